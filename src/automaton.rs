@@ -1,27 +1,199 @@
+use crate::boundary::Boundary;
 use crate::field::Field;
+use crate::neighborhood::Neighborhood;
+use crate::pattern::CharClass;
 use crate::rule::Rule;
+use crate::update::UpdateMode;
+use std::collections::HashMap;
+use std::fmt;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Automaton {
+    #[serde(default)]
+    pub neighborhood: Neighborhood,
+    #[serde(default)]
+    pub boundary: Boundary,
+    #[serde(default)]
+    pub policy: MatchPolicy,
+    // Whether players may give their rules a symmetry.
+    #[serde(default)]
+    pub allow_symmetry: bool,
+    // Pattern symbols that stand for a set of bytes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<CharClass>,
+    #[serde(default)]
+    pub update: UpdateMode,
     pub rules: Vec<Rule>,
 }
 
+// How a cell is decided when several rules match it.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchPolicy {
+    // The first matching rule in order wins.
+    #[default]
+    First,
+    // All matching rules have to agree on the result. Cells where they do not
+    // stay unchanged and are reported as conflicts.
+    Unique,
+    // The last matching rule in order wins.
+    Last,
+}
+
+impl MatchPolicy {
+    pub const ALL: [MatchPolicy; 3] = [MatchPolicy::First, MatchPolicy::Unique, MatchPolicy::Last];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MatchPolicy::First => return "first match",
+            MatchPolicy::Unique => return "all must agree",
+            MatchPolicy::Last => return "last match",
+        }
+    }
+}
+
+// A cell where matching rules disagreed under `MatchPolicy::Unique`.
+#[derive(Clone)]
+pub struct Conflict {
+    pub x: usize,
+    pub y: usize,
+    pub rules: Vec<usize>,
+}
+
+// Positions and rules are counted from 1 like everywhere players see them.
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rules = self.rules.iter().map(|r| (r + 1).to_string());
+        return write!(
+            f,
+            "conflict at ({}, {}): rules {} disagree",
+            self.x + 1,
+            self.y + 1,
+            rules.collect::<Vec<_>>().join(", ")
+        );
+    }
+}
+
+// Which rule produced a cell and how its variables were bound.
+#[derive(Clone)]
+pub struct Firing {
+    pub rule: usize,
+    pub bindings: HashMap<u8, u8>,
+}
+
+// One entry per cell, indexed like `Field::data`.
+pub type Trace = Vec<Vec<Option<Firing>>>;
+
 impl Automaton {
     //pub fn new(rules: Vec<Rule>) -> Self {
     //    Self { rules }
     //}
 
-    pub fn step(&self, field: &Field) -> Field {
+    // The cells a rule sees around (x, y) in step `step`. When the update
+    // mode splits the field into blocks, cells of other blocks read like
+    // cells outside the field.
+    pub fn cells_around(&self, field: &Field, x: usize, y: usize, step: usize) -> Vec<u8> {
+        let cells = field.neighborhood(x, y, self.neighborhood, self.boundary);
+        let offset = match self.update.block_offset(step) {
+            Some(offset) => offset,
+            None => return cells,
+        };
+        let block = |c: isize| (c - offset).div_euclid(2);
+        let (x, y) = (x as isize, y as isize);
+        return self
+            .neighborhood
+            .offsets()
+            .zip(cells)
+            .map(|((xo, yo), cell)| {
+                if block(x + xo) == block(x) && block(y + yo) == block(y) {
+                    return cell;
+                }
+                return self.boundary.fill();
+            })
+            .collect();
+    }
+
+    // Decides the cell at (x, y) in step `step`: the rule that fires there, if
+    // any, or the rules that disagree about it. `perms` holds every rule's
+    // orientations.
+    fn decide(
+        &self,
+        field: &Field,
+        perms: &[Vec<Vec<usize>>],
+        x: usize,
+        y: usize,
+        step: usize,
+    ) -> Result<Option<(u8, Firing)>, Conflict> {
+        let cells = self.cells_around(field, x, y, step);
+        let mut matches = self.rules.iter().enumerate().filter_map(|(i, r)| {
+            let (res, bindings) = r.apply(&cells, &perms[i], &self.classes)?;
+            return Some((res, Firing { rule: i, bindings }));
+        });
+        match self.policy {
+            MatchPolicy::First => return Ok(matches.next()),
+            MatchPolicy::Last => return Ok(matches.next_back()),
+            MatchPolicy::Unique => {
+                let all = matches.collect::<Vec<_>>();
+                if all.iter().any(|(res, _)| *res != all[0].0) {
+                    let rules = all.iter().map(|(_, f)| f.rule).collect();
+                    return Err(Conflict { x, y, rules });
+                }
+                return Ok(all.into_iter().next());
+            }
+        }
+    }
+
+    // Computes the field after `field`, which is the one reached after
+    // `step` steps. Only some update modes care about the step number.
+    pub fn step(&self, field: &Field, step: usize) -> Field {
+        return self.advance(field, step, None).0;
+    }
+
+    // Same as `step`, but also returns the cells where the rules disagreed.
+    pub fn step_checked(&self, field: &Field, step: usize) -> (Field, Vec<Conflict>) {
+        return self.advance(field, step, None);
+    }
+
+    // Same as `step_checked`, but also records which rule fired for every
+    // cell.
+    pub fn step_traced(&self, field: &Field, step: usize) -> (Field, Trace, Vec<Conflict>) {
+        let mut trace = vec![vec![None; field.w]; field.h];
+        let (next_field, conflicts) = self.advance(field, step, Some(&mut trace));
+        return (next_field, trace, conflicts);
+    }
+
+    // Runs one step, filling in `trace` if one is given.
+    fn advance(
+        &self,
+        field: &Field,
+        step: usize,
+        mut trace: Option<&mut Trace>,
+    ) -> (Field, Vec<Conflict>) {
+        let perms = self
+            .rules
+            .iter()
+            .map(|r| r.permutations(self.neighborhood))
+            .collect::<Vec<_>>();
         let mut next_field = field.clone();
-        for (x, y) in field.coords() {
-            for r in self.rules.iter() {
-                if let Some(res) = r.apply(field.neighborhood(x, y)) {
-                    next_field.set(x, y, res);
-                    break;
+        let mut conflicts = vec![];
+        for batch in self.update.schedule(field) {
+            let decided = batch
+                .iter()
+                .map(|&(x, y)| (x, y, self.decide(&next_field, &perms, x, y, step)))
+                .collect::<Vec<_>>();
+            for (x, y, decision) in decided {
+                match decision {
+                    Ok(Some((res, firing))) => {
+                        next_field.set(x, y, res);
+                        if let Some(trace) = trace.as_deref_mut() {
+                            trace[y][x] = Some(firing);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(conflict) => conflicts.push(conflict),
                 }
             }
         }
-        return next_field;
+        return (next_field, conflicts);
     }
 
     //pub fn add_rule(&mut self, pattern: &[u8], replace: u8, mutable: bool) {
@@ -32,3 +204,89 @@ impl Automaton {
     //    });
     //}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auto(policy: MatchPolicy, rules: Vec<Rule>) -> Automaton {
+        return Automaton {
+            neighborhood: Neighborhood::Moore,
+            boundary: Boundary::Constant(b'#'),
+            policy,
+            allow_symmetry: false,
+            classes: vec![],
+            update: UpdateMode::default(),
+            rules,
+        };
+    }
+
+    fn field(row: &[u8]) -> Field {
+        return Field {
+            w: row.len(),
+            h: 1,
+            data: vec![row.to_vec()],
+        };
+    }
+
+    // Rule 0 matches every cell, rule 1 only blank ones.
+    fn overlapping(second: u8) -> Vec<Rule> {
+        return vec![
+            Rule::new(b"_________".to_vec(), b'a'),
+            Rule::new(b"____ ____".to_vec(), second),
+        ];
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let a = auto(MatchPolicy::First, overlapping(b'b'));
+        assert!(a.step(&field(b" x"), 0) == field(b"aa"));
+    }
+
+    #[test]
+    fn last_match_wins() {
+        let a = auto(MatchPolicy::Last, overlapping(b'b'));
+        assert!(a.step(&field(b" x"), 0) == field(b"ba"));
+    }
+
+    #[test]
+    fn unique_rules_that_agree() {
+        let a = auto(MatchPolicy::Unique, overlapping(b'a'));
+        let (next, conflicts) = a.step_checked(&field(b" x"), 0);
+        assert!(next == field(b"aa"));
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn unique_rules_that_disagree() {
+        let a = auto(MatchPolicy::Unique, overlapping(b'b'));
+        let (next, conflicts) = a.step_checked(&field(b" x"), 0);
+        // The conflicting cell stays, the other one is decided by rule 0 alone.
+        assert!(next == field(b" a"));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].x, conflicts[0].y), (0, 0));
+        assert_eq!(conflicts[0].rules, vec![0, 1]);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "conflict at (1, 1): rules 1, 2 disagree"
+        );
+    }
+
+    #[test]
+    fn trace_records_firings() {
+        // A blank to the right turns any cell into a y.
+        let a = auto(
+            MatchPolicy::First,
+            vec![Rule::new(b"____A ___".to_vec(), b'y')],
+        );
+        let (next, trace, conflicts) = a.step_traced(&field(b"x "), 0);
+        assert!(next == field(b"y "));
+        assert!(conflicts.is_empty());
+        let firing = trace[0][0].as_ref().unwrap();
+        assert_eq!(firing.rule, 0);
+        assert_eq!(firing.bindings.get(&b'A'), Some(&b'x'));
+        // The boundary is not blank, so nothing fires at the right edge.
+        assert!(trace[0][1].is_none());
+        assert!(next == a.step(&field(b"x "), 0));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::grid;
+
+// What a rule sees when its neighborhood reaches past the edge of the field.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Boundary {
+    // Every cell outside the field holds the same byte.
+    Constant(
+        #[serde(
+            serialize_with = "grid::serialize_byte",
+            deserialize_with = "grid::deserialize_byte"
+        )]
+        u8,
+    ),
+    // Leaving the field on one side enters it again on the opposite side.
+    Wrap,
+    // The field is reflected at its edges, so the edge cells repeat.
+    Mirror,
+}
+
+impl Default for Boundary {
+    fn default() -> Self {
+        return Boundary::Constant(0x20);
+    }
+}
+
+impl Boundary {
+    pub fn name(&self) -> String {
+        match self {
+            Boundary::Constant(b) => return format!("constant '{}'", *b as char),
+            Boundary::Wrap => return "wrap".to_string(),
+            Boundary::Mirror => return "mirror".to_string(),
+        }
+    }
+
+    // The byte used for cells that do not map back into the field.
+    pub fn fill(&self) -> u8 {
+        match self {
+            Boundary::Constant(b) => return *b,
+            _ => return 0x20,
+        }
+    }
+
+    // Maps a coordinate along an axis of `len` cells into the field. Returns
+    // None if it lies outside and `fill` is to be used instead.
+    pub fn coord(&self, c: isize, len: usize) -> Option<usize> {
+        let len = len as isize;
+        if c >= 0 && c < len {
+            return Some(c as usize);
+        }
+        if len == 0 {
+            return None;
+        }
+        match self {
+            Boundary::Constant(_) => return None,
+            Boundary::Wrap => return Some(c.rem_euclid(len) as usize),
+            Boundary::Mirror => {
+                let c = c.rem_euclid(2 * len);
+                if c >= len {
+                    return Some((2 * len - 1 - c) as usize);
+                }
+                return Some(c as usize);
+            }
+        }
+    }
+
+    // The next mode when cycling through them in the editor. Constant keeps
+    // its fill byte only as long as it stays selected.
+    pub fn next(&self) -> Self {
+        match self {
+            Boundary::Constant(_) => return Boundary::Wrap,
+            Boundary::Wrap => return Boundary::Mirror,
+            Boundary::Mirror => return Boundary::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Field;
+    use crate::neighborhood::Neighborhood;
+
+    #[test]
+    fn inside_is_unchanged() {
+        for boundary in [Boundary::default(), Boundary::Wrap, Boundary::Mirror] {
+            assert_eq!(boundary.coord(0, 4), Some(0));
+            assert_eq!(boundary.coord(3, 4), Some(3));
+        }
+    }
+
+    #[test]
+    fn constant() {
+        let boundary = Boundary::Constant(b'#');
+        assert_eq!(boundary.coord(-1, 4), None);
+        assert_eq!(boundary.coord(4, 4), None);
+        assert_eq!(boundary.fill(), b'#');
+    }
+
+    #[test]
+    fn wrap() {
+        assert_eq!(Boundary::Wrap.coord(-1, 4), Some(3));
+        assert_eq!(Boundary::Wrap.coord(4, 4), Some(0));
+        assert_eq!(Boundary::Wrap.coord(-6, 4), Some(2));
+    }
+
+    #[test]
+    fn mirror() {
+        assert_eq!(Boundary::Mirror.coord(-1, 4), Some(0));
+        assert_eq!(Boundary::Mirror.coord(-2, 4), Some(1));
+        assert_eq!(Boundary::Mirror.coord(4, 4), Some(3));
+        assert_eq!(Boundary::Mirror.coord(5, 4), Some(2));
+        // Past a whole reflection the field starts over.
+        assert_eq!(Boundary::Mirror.coord(8, 4), Some(0));
+        assert_eq!(Boundary::Mirror.coord(-3, 1), Some(0));
+    }
+
+    #[test]
+    fn empty_axis() {
+        assert_eq!(Boundary::Wrap.coord(0, 0), None);
+        assert_eq!(Boundary::Mirror.coord(-1, 0), None);
+    }
+
+    #[test]
+    fn neighborhood_at_the_corner() {
+        let field = Field {
+            w: 2,
+            h: 2,
+            data: vec![b"ab".to_vec(), b"cd".to_vec()],
+        };
+        let cells = |boundary| field.neighborhood(0, 0, Neighborhood::Moore, boundary);
+        assert_eq!(cells(Boundary::Constant(b'.')), b"....ab.cd".to_vec());
+        assert_eq!(cells(Boundary::Wrap), b"dcdbabdcd".to_vec());
+        assert_eq!(cells(Boundary::Mirror), b"aabaabccd".to_vec());
+    }
+
+    #[test]
+    fn next_cycles_through_all() {
+        let mut boundary = Boundary::Constant(b'#');
+        let mut names = vec![];
+        for _ in 0..3 {
+            boundary = boundary.next();
+            names.push(boundary.name());
+        }
+        assert_eq!(names, vec!["wrap", "mirror", "constant ' '"]);
+    }
+}
@@ -0,0 +1,102 @@
+// The levels shipped with the game. They are compiled into the binary so it
+// runs from any directory, levels on disk can replace or extend them.
+use rulez::validate;
+use rulez::{Level, LevelError};
+use std::fs;
+use std::path::Path;
+
+// The progress source of the built-in levels, see `Game::levels`.
+pub const BUILTIN: &str = "builtin";
+
+// Every file in the level directory, a test checks that none is missing.
+const BUILTIN_LEVELS: &[(&str, &str)] = &[
+    ("builtin:level1.ron", include_str!("../level/level1.ron")),
+    ("builtin:level2.ron", include_str!("../level/level2.ron")),
+    ("builtin:level3.ron", include_str!("../level/level3.ron")),
+    ("builtin:level4.ron", include_str!("../level/level4.ron")),
+];
+
+pub fn builtin() -> Result<Vec<(String, Level)>, Vec<LevelError>> {
+    let mut levels = vec![];
+    let mut errors = vec![];
+    for (path, contents) in BUILTIN_LEVELS.iter() {
+        match validate::parse_level(path, contents) {
+            Ok(lvl) => {
+                errors.extend(validate::validate_level(path, &lvl));
+                levels.push((path.to_string(), lvl));
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    if errors.is_empty() {
+        return Ok(levels);
+    }
+    return Err(errors);
+}
+
+// Adds `extra` to `levels`. A level with an id that is already taken
+// replaces the one in `levels`, ids repeated within `extra` are kept so
+// they can be reported. Copies of a level in `levels`, like the shipped
+// level directory, are dropped so the level keeps its progress.
+pub fn merge(
+    mut levels: Vec<(String, Level)>,
+    extra: Vec<(String, Level)>,
+) -> Vec<(String, Level)> {
+    let same = |a: &Level, b: &Level| ron::ser::to_string(a).ok() == ron::ser::to_string(b).ok();
+    let extra = extra
+        .into_iter()
+        .filter(|(_, e)| !levels.iter().any(|(_, lvl)| same(lvl, e)))
+        .collect::<Vec<_>>();
+    levels.retain(|(_, lvl)| !extra.iter().any(|(_, e)| e.id == lvl.id));
+    levels.extend(extra);
+    levels.sort_by_key(|(_, lvl)| lvl.id);
+    return levels;
+}
+
+// Where the progress of the level loaded from `path` is saved: levels of a
+// pack under the pack, levels from a directory under the directory.
+pub fn source(pack: Option<&str>, path: &str) -> String {
+    if let Some(pack) = pack {
+        return format!("pack:{}", canonical(pack));
+    }
+    if path.starts_with("builtin:") {
+        return BUILTIN.to_string();
+    }
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    return format!("dir:{}", canonical(&dir.to_string_lossy()));
+}
+
+fn canonical(path: &str) -> String {
+    return fs::canonicalize(path)
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| path.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `BUILTIN_LEVELS` is written by hand, so a level added to the level
+    // directory has to be added there too.
+    #[test]
+    fn every_level_file_is_built_in() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("level");
+        let mut files = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".ron"))
+            .collect::<Vec<_>>();
+        files.sort();
+        let mut builtin = BUILTIN_LEVELS
+            .iter()
+            .map(|(path, _)| path.trim_start_matches("builtin:").to_string())
+            .collect::<Vec<_>>();
+        builtin.sort();
+        assert_eq!(files, builtin);
+    }
+
+    #[test]
+    fn builtin_levels_are_valid() {
+        assert!(builtin().is_ok());
+    }
+}
@@ -0,0 +1,175 @@
+// Command line parsing. Options may appear before or after the subcommand,
+// anything that is not an option is a level file.
+pub const USAGE: &str = "usage: automaton [<command>] [options] [<level file>...]
+
+commands:
+  play                  play the campaign in the terminal (default)
+  run <level>           simulate a level without the terminal
+  validate              check level files for mistakes
+  solve                 search for minimal rule sets
+  convert               rewrite level files in the grid format
+
+options:
+  --levels <dir>        directory with levels that replace built-in ones with
+                        the same id or add to them (default: level, if present)
+  --no-builtin          only use the levels from the directory
+  --pack <file>         use the levels of a pack instead
+  --level <id>          start at (play), simulate (run) or solve only this level
+  --speed <ms>          time between two animation steps (default: 500)
+  --rules <file>        rules to put into the level's mutable slots (run)
+  --max-steps <n>       steps before giving up (run: 100, solve: 20)
+  --max-literals <n>    non-wildcard cells per rule to try (solve, default: 2)
+  -h, --help            show this help";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Play,
+    Run,
+    Validate,
+    Solve,
+    Convert,
+}
+
+impl Command {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "play" => return Some(Command::Play),
+            "run" => return Some(Command::Run),
+            "validate" => return Some(Command::Validate),
+            "solve" => return Some(Command::Solve),
+            "convert" => return Some(Command::Convert),
+            _ => return None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Play => return "play",
+            Command::Run => return "run",
+            Command::Validate => return "validate",
+            Command::Solve => return "solve",
+            Command::Convert => return "convert",
+        }
+    }
+}
+
+pub struct Options {
+    pub command: Command,
+    pub levels: Option<String>,
+    pub no_builtin: bool,
+    pub pack: Option<String>,
+    pub level: Option<usize>,
+    pub speed: Option<u64>,
+    pub rules: Option<String>,
+    pub max_steps: Option<usize>,
+    pub max_literals: Option<usize>,
+    pub paths: Vec<String>,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        return Self {
+            command: Command::Play,
+            levels: None,
+            no_builtin: false,
+            pack: None,
+            level: None,
+            speed: None,
+            rules: None,
+            max_steps: None,
+            max_literals: None,
+            paths: vec![],
+            help: false,
+        };
+    }
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut opts = Options::default();
+        let mut command = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                return args
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} needs a value", arg));
+            };
+            match arg.as_str() {
+                "-h" | "--help" => opts.help = true,
+                "--levels" => opts.levels = Some(value()?),
+                "--no-builtin" => opts.no_builtin = true,
+                "--pack" => opts.pack = Some(value()?),
+                "--level" => opts.level = Some(number(arg, &value()?)?),
+                "--speed" => opts.speed = Some(number(arg, &value()?)?),
+                "--rules" => opts.rules = Some(value()?),
+                "--max-steps" => opts.max_steps = Some(number(arg, &value()?)?),
+                "--max-literals" => opts.max_literals = Some(number(arg, &value()?)?),
+                flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+                name if command.is_none() && opts.paths.is_empty() => match Command::parse(name) {
+                    Some(cmd) => command = Some(cmd),
+                    None => opts.paths.push(name.to_string()),
+                },
+                path => opts.paths.push(path.to_string()),
+            }
+        }
+        opts.command = command.unwrap_or(Command::Play);
+        opts.check()?;
+        return Ok(opts);
+    }
+
+    // Rejects options that the chosen command would silently ignore.
+    fn check(&self) -> Result<(), String> {
+        use Command::*;
+        let cmd = self.command;
+        let unused = [
+            ("--speed", self.speed.is_some(), cmd != Play),
+            ("--rules", self.rules.is_some(), cmd != Run),
+            (
+                "--max-steps",
+                self.max_steps.is_some(),
+                cmd != Run && cmd != Solve,
+            ),
+            ("--max-literals", self.max_literals.is_some(), cmd != Solve),
+            (
+                "--level",
+                self.level.is_some(),
+                cmd == Validate || cmd == Convert,
+            ),
+            ("--pack", self.pack.is_some(), cmd == Convert),
+            ("--no-builtin", self.no_builtin, cmd == Convert),
+        ];
+        if let Some((flag, _, _)) = unused.iter().find(|(_, set, unused)| *set && *unused) {
+            return Err(format!("{} cannot be used with {}", flag, cmd.name()));
+        }
+        if self.speed == Some(0) {
+            return Err("--speed must be at least 1".to_string());
+        }
+        if cmd == Play && !self.paths.is_empty() {
+            return Err(format!("unknown command {}", self.paths[0]));
+        }
+        if cmd == Run && self.paths.len() > 1 {
+            return Err("run takes a single level file".to_string());
+        }
+        if self.pack.is_some() && (self.levels.is_some() || self.no_builtin) {
+            return Err("--pack cannot be combined with --levels or --no-builtin".to_string());
+        }
+        if !self.paths.is_empty() && (self.pack.is_some() || self.level.is_some()) {
+            return Err("level files cannot be combined with --pack or --level".to_string());
+        }
+        return Ok(());
+    }
+
+    // Where level files are read from and new levels are saved to.
+    pub fn levels_dir(&self) -> &str {
+        return self.levels.as_deref().unwrap_or("level");
+    }
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    return value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got \"{}\"", flag, value));
+}
@@ -1,4 +1,4 @@
-use crate::level::Level;
+use rulez::Level;
 
 pub enum CursorPos {
     Pattern { i: isize, x: isize, y: isize },
@@ -8,29 +8,127 @@ pub enum CursorPos {
 
 impl CursorPos {
     pub fn in_state(&self) -> bool {
-        match self {
-            CursorPos::State { .. } => true,
-            _ => false,
+        return matches!(self, CursorPos::State { .. });
+    }
+
+    // Index of the rule the cursor is on.
+    pub fn rule(&self) -> Option<usize> {
+        match *self {
+            CursorPos::Pattern { i, .. } | CursorPos::Replace { i } => return Some(i as usize),
+            CursorPos::State { .. } => return None,
+        }
+    }
+
+    // The first pattern cell of the first rule.
+    pub fn start(lvl: &Level) -> Self {
+        return Self::horizontal(lvl, 0, -1, 0, 1);
+    }
+
+    // Index of the pattern cell under the cursor.
+    pub fn cell(&self, lvl: &Level) -> Option<usize> {
+        match *self {
+            CursorPos::Pattern { x, y, .. } => {
+                return lvl.auto.neighborhood.index(x as usize, y as usize)
+            }
+            _ => return None,
+        }
+    }
+
+    pub fn is_locked(&self, lvl: &Level) -> bool {
+        match *self {
+            CursorPos::Pattern { i, .. } => {
+                let cell = self.cell(lvl).unwrap();
+                return lvl.auto.rules[i as usize].is_cell_locked(cell);
+            }
+            CursorPos::Replace { i } => return !lvl.auto.rules[i as usize].mutable,
+            CursorPos::State { .. } => return false,
+        }
+    }
+
+    // Repeats `step` until the cursor lands on a cell the player may edit.
+    // If every cell in that direction is locked this is just a single
+    // `step`. Moving up stops at the top row and down at the state, so
+    // vertical moves end there at the latest.
+    pub fn skip_locked(&self, lvl: &Level, step: fn(&Self, &Level) -> Self) -> Self {
+        let mut cur = step(self, lvl);
+        for _ in 0..lvl.auto.rules.len() * lvl.auto.neighborhood.cells() {
+            if !cur.is_locked(lvl) {
+                return cur;
+            }
+            cur = step(&cur, lvl);
         }
+        return step(self, lvl);
     }
 
-    pub fn up(&self, _lvl: &Level) -> Self {
+    fn in_pattern(lvl: &Level, x: isize, y: isize) -> bool {
+        return x >= 0
+            && y >= 0
+            && lvl
+                .auto
+                .neighborhood
+                .index(x as usize, y as usize)
+                .is_some();
+    }
+
+    // Moves vertically inside a rule's pattern, jumping over the cells of the
+    // bounding box that are not part of the neighborhood.
+    fn vertical(lvl: &Level, x: isize, y: isize, dy: isize) -> Option<isize> {
+        let h = lvl.auto.neighborhood.height() as isize;
+        let mut y = y + dy;
+        while y >= 0 && y < h {
+            if Self::in_pattern(lvl, x, y) {
+                return Some(y);
+            }
+            y += dy;
+        }
+        return None;
+    }
+
+    // Moves horizontally, wrapping over to the neighboring rules.
+    fn horizontal(lvl: &Level, i: isize, x: isize, y: isize, dx: isize) -> Self {
+        let len = lvl.auto.rules.len() as isize;
+        let w = lvl.auto.neighborhood.width() as isize;
+        let (mut i, mut x) = (i, x);
+        for _ in 0..len * w {
+            x += dx;
+            if x < 0 {
+                i = (len + i - 1) % len;
+                x = w - 1;
+            } else if x >= w {
+                i = (i + 1) % len;
+                x = 0;
+            }
+            if Self::in_pattern(lvl, x, y) {
+                break;
+            }
+        }
+        return CursorPos::Pattern { i, x, y };
+    }
+
+    pub fn up(&self, lvl: &Level) -> Self {
         use CursorPos::*;
+        let nb = lvl.auto.neighborhood;
         match *self {
             Pattern { i, x, y } => Pattern {
                 i,
                 x,
-                y: (y as usize).saturating_sub(1) as isize,
+                y: Self::vertical(lvl, x, y, -1).unwrap_or(y),
+            },
+            Replace { i } => Pattern {
+                i,
+                x: nb.width() as isize / 2,
+                y: nb.height() as isize - 1,
             },
-            Replace { i } => Pattern { i: i, x: 1, y: 2 },
             State { last_i } => Replace { i: last_i },
         }
     }
-    pub fn down(&self, _lvl: &Level) -> Self {
+    pub fn down(&self, lvl: &Level) -> Self {
         use CursorPos::*;
         match *self {
-            Pattern { i, x, y } if y < 2 => Pattern { i, x, y: y + 1 },
-            Pattern { i, .. } => Replace { i },
+            Pattern { i, x, y } => match Self::vertical(lvl, x, y, 1) {
+                Some(y) => Pattern { i, x, y },
+                None => Replace { i },
+            },
             Replace { i } => State { last_i: i },
             State { last_i } => State { last_i },
         }
@@ -39,12 +137,7 @@ impl CursorPos {
         use CursorPos::*;
         let len = lvl.auto.rules.len() as isize;
         match *self {
-            Pattern { i, x: 0, y } => Pattern {
-                i: (len + i - 1) % len,
-                x: 2,
-                y,
-            },
-            Pattern { i, x, y } => Pattern { i, x: x - 1, y },
+            Pattern { i, x, y } => Self::horizontal(lvl, i, x, y, -1),
             Replace { i } => Replace {
                 i: (len + i - 1) % len,
             },
@@ -55,12 +148,7 @@ impl CursorPos {
         use CursorPos::*;
         let len = lvl.auto.rules.len() as isize;
         match *self {
-            Pattern { i, x: 2, y } => Pattern {
-                i: (i + 1) % len,
-                x: 0,
-                y,
-            },
-            Pattern { i, x, y } => Pattern { i, x: x + 1, y },
+            Pattern { i, x, y } => Self::horizontal(lvl, i, x, y, 1),
             Replace { i } => Replace { i: (i + 1) % len },
             State { last_i } => State { last_i },
         }
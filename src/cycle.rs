@@ -0,0 +1,165 @@
+use crate::field::Field;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Stuck {
+    FixedPoint { step: usize },
+    Cycle { start: usize, period: usize },
+}
+
+impl fmt::Display for Stuck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stuck::FixedPoint { step } => write!(f, "field stopped changing at step {}", step),
+            Stuck::Cycle { start, period } => {
+                write!(f, "loops with period {} from step {}", period, start)
+            }
+        }
+    }
+}
+
+// Remembers every field of a run to notice when the automaton can no longer
+// reach anything new. An update mode with several phases only repeats itself
+// when a field comes back in the same phase, see `UpdateMode::phases`.
+pub struct CycleDetector {
+    phases: usize,
+    seen: HashMap<(usize, Field), usize>,
+    last: Field,
+    // How many steps in a row left the field as it was.
+    unchanged: usize,
+}
+
+impl CycleDetector {
+    pub fn new(start: &Field, phases: usize) -> Self {
+        let mut seen = HashMap::new();
+        seen.insert((0, start.clone()), 0);
+        return Self {
+            phases,
+            seen,
+            last: start.clone(),
+            unchanged: 0,
+        };
+    }
+
+    // Feeds the field produced at `step`. Steps have to be fed in order.
+    pub fn check(&mut self, step: usize, field: &Field) -> Option<Stuck> {
+        if *field == self.last {
+            self.unchanged += 1;
+        } else {
+            self.unchanged = 0;
+            self.last = field.clone();
+        }
+        // Every phase has had its turn without changing anything.
+        if self.unchanged >= self.phases {
+            return Some(Stuck::FixedPoint {
+                step: step - self.unchanged,
+            });
+        }
+        let key = (step % self.phases, field.clone());
+        match self.seen.get(&key) {
+            Some(&first) => {
+                return Some(Stuck::Cycle {
+                    start: first,
+                    period: step - first,
+                })
+            }
+            None => {
+                self.seen.insert(key, step);
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(cell: u8) -> Field {
+        return Field {
+            w: 1,
+            h: 1,
+            data: vec![vec![cell]],
+        };
+    }
+
+    #[test]
+    fn new_fields_are_not_stuck() {
+        let mut cycles = CycleDetector::new(&field(b'a'), 1);
+        assert!(cycles.check(1, &field(b'b')).is_none());
+        assert!(cycles.check(2, &field(b'c')).is_none());
+    }
+
+    #[test]
+    fn fixed_point() {
+        let mut cycles = CycleDetector::new(&field(b'a'), 1);
+        assert!(cycles.check(1, &field(b'b')).is_none());
+        let stuck = cycles.check(2, &field(b'b'));
+        assert!(stuck == Some(Stuck::FixedPoint { step: 1 }));
+        assert_eq!(
+            stuck.unwrap().to_string(),
+            "field stopped changing at step 1"
+        );
+    }
+
+    #[test]
+    fn fixed_point_at_the_start() {
+        let mut cycles = CycleDetector::new(&field(b'a'), 1);
+        let stuck = cycles.check(1, &field(b'a'));
+        assert!(stuck == Some(Stuck::FixedPoint { step: 0 }));
+    }
+
+    #[test]
+    fn cycle() {
+        let mut cycles = CycleDetector::new(&field(b'a'), 1);
+        assert!(cycles.check(1, &field(b'b')).is_none());
+        assert!(cycles.check(2, &field(b'c')).is_none());
+        assert!(cycles.check(3, &field(b'd')).is_none());
+        let stuck = cycles.check(4, &field(b'b'));
+        assert!(
+            stuck
+                == Some(Stuck::Cycle {
+                    start: 1,
+                    period: 3
+                })
+        );
+        assert_eq!(
+            stuck.unwrap().to_string(),
+            "loops with period 3 from step 1"
+        );
+    }
+
+    #[test]
+    fn phases_have_to_match() {
+        let mut cycles = CycleDetector::new(&field(b'a'), 2);
+        assert!(cycles.check(1, &field(b'b')).is_none());
+        assert!(cycles.check(2, &field(b'c')).is_none());
+        // Back to the start, but in the other phase.
+        assert!(cycles.check(3, &field(b'a')).is_none());
+    }
+
+    #[test]
+    fn fixed_point_over_phases() {
+        let mut cycles = CycleDetector::new(&field(b'a'), 2);
+        assert!(cycles.check(1, &field(b'b')).is_none());
+        // One phase alone leaving the field as it is is not enough.
+        assert!(cycles.check(2, &field(b'b')).is_none());
+        let stuck = cycles.check(3, &field(b'b'));
+        assert!(stuck == Some(Stuck::FixedPoint { step: 1 }));
+    }
+
+    #[test]
+    fn cycle_over_phases() {
+        let mut cycles = CycleDetector::new(&field(b'a'), 2);
+        assert!(cycles.check(1, &field(b'b')).is_none());
+        let stuck = cycles.check(2, &field(b'a'));
+        assert!(
+            stuck
+                == Some(Stuck::Cycle {
+                    start: 0,
+                    period: 2
+                })
+        );
+    }
+}
@@ -0,0 +1,553 @@
+use crate::cursor_pos::CursorPos;
+use crate::game_state::GameState;
+use crate::playing_ui::{self, Out, PlayingUI};
+use rulez::{
+    Automaton, Boundary, CharClass, Count, Field, Level, MatchPolicy, Neighborhood, Rule, Symmetry,
+    UpdateMode,
+};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use termion::event::{Event, Key};
+
+const MAX_SIZE: usize = 40;
+
+pub enum Focus {
+    Start,
+    Goal,
+    Rules,
+    Title,
+    Description,
+}
+
+pub struct EditorUI {
+    pub lvl: Level,
+    pub focus: Focus,
+    pub x: usize,
+    pub y: usize,
+    pub rule_cursor: CursorPos,
+    pub dir: String,
+    pub message: String,
+    pub pending_distinct: Option<u8>,
+    pub pending_count: String,
+    // The symbol of the character class being edited, see `edit_class`.
+    pub pending_class: Option<u8>,
+    // The level and its path after a successful save, until the game picks
+    // it up and adds it to the level list.
+    pub saved: Option<(String, Level)>,
+}
+
+impl EditorUI {
+    pub fn new(id: usize, dir: &str) -> Self {
+        let empty = Field {
+            w: 7,
+            h: 7,
+            data: vec![vec![0x20; 7]; 7],
+        };
+        let lvl = Level {
+            title: String::new(),
+            description: String::new(),
+            difficulty: 0,
+            hints: vec![],
+            start: empty.clone(),
+            goal: empty,
+            auto: Automaton {
+                neighborhood: Neighborhood::default(),
+                boundary: Boundary::default(),
+                policy: MatchPolicy::default(),
+                allow_symmetry: false,
+                classes: vec![],
+                update: UpdateMode::default(),
+                rules: vec![Self::empty_rule(Neighborhood::default())],
+            },
+            id,
+        };
+        let rule_cursor = CursorPos::start(&lvl);
+        return Self {
+            lvl,
+            focus: Focus::Start,
+            x: 0,
+            y: 0,
+            rule_cursor,
+            dir: dir.to_string(),
+            message: String::new(),
+            pending_distinct: None,
+            pending_count: String::new(),
+            pending_class: None,
+            saved: None,
+        };
+    }
+
+    fn empty_rule(nb: Neighborhood) -> Rule {
+        return Rule::new(vec![0x20; nb.cells()], 0x20);
+    }
+
+    pub fn path(&self) -> String {
+        return format!("{}/level{}.ron", self.dir, self.lvl.id);
+    }
+
+    pub fn save(&mut self) {
+        let path = self.path();
+        if Path::new(&path).exists() {
+            self.message = format!("{} already exists", path);
+            return;
+        }
+        let pretty = ron::ser::PrettyConfig::default();
+        let res = ron::ser::to_string_pretty(&self.lvl, pretty)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                return fs::create_dir_all(&self.dir)
+                    .and_then(|_| fs::write(&path, contents))
+                    .map_err(|e| e.to_string());
+            });
+        self.message = match res {
+            Ok(()) => {
+                self.saved = Some((path.clone(), self.lvl.clone()));
+                format!("saved {}", path)
+            }
+            Err(e) => format!("could not save {}: {}", path, e),
+        };
+    }
+
+    fn field_mut(&mut self) -> Option<&mut Field> {
+        match self.focus {
+            Focus::Start => return Some(&mut self.lvl.start),
+            Focus::Goal => return Some(&mut self.lvl.goal),
+            Focus::Rules | Focus::Title | Focus::Description => return None,
+        }
+    }
+
+    fn text_mut(&mut self) -> Option<&mut String> {
+        match self.focus {
+            Focus::Title => return Some(&mut self.lvl.title),
+            Focus::Description => return Some(&mut self.lvl.description),
+            Focus::Start | Focus::Goal | Focus::Rules => return None,
+        }
+    }
+
+    // Where typing continues the title or description, at the end of what is
+    // shown of it by `PlayingUI::draw_level_description`.
+    fn text_cursor(&self) -> (u16, u16) {
+        if let Focus::Title = self.focus {
+            return (2 + self.lvl.name().chars().count() as u16, 2);
+        }
+        let top = if self.lvl.difficulty > 0 { 4 } else { 3 };
+        let description = &self.lvl.description;
+        let mut lines = playing_ui::wrap(description, PlayingUI::text_width(&self.lvl));
+        if description.is_empty() || description.ends_with('\n') {
+            lines.push(String::new());
+        }
+        let last = lines.len() - 1;
+        return (2 + lines[last].chars().count() as u16, top + last as u16);
+    }
+
+    pub fn resize(&mut self, w: usize, h: usize) {
+        let w = w.clamp(1, MAX_SIZE);
+        let h = h.clamp(1, MAX_SIZE);
+        self.lvl.start.resize(w, h);
+        self.lvl.goal.resize(w, h);
+        self.x = self.x.min(w - 1);
+        self.y = self.y.min(h - 1);
+    }
+
+    pub fn resize_rules(&mut self, n: usize) {
+        let n = n.max(1);
+        self.lvl
+            .auto
+            .rules
+            .resize(n, Self::empty_rule(self.lvl.auto.neighborhood));
+        self.rule_cursor = match self.rule_cursor {
+            CursorPos::Pattern { i, x, y } => CursorPos::Pattern {
+                i: i.min(n as isize - 1),
+                x,
+                y,
+            },
+            CursorPos::Replace { i } => CursorPos::Replace {
+                i: i.min(n as isize - 1),
+            },
+            CursorPos::State { last_i } => CursorPos::Replace { i: last_i },
+        };
+    }
+
+    pub fn toggle_lock(&mut self) {
+        let i = match self.rule_cursor {
+            CursorPos::Pattern { i, .. } | CursorPos::Replace { i } => i as usize,
+            CursorPos::State { .. } => return,
+        };
+        let rule = &mut self.lvl.auto.rules[i];
+        rule.mutable = !rule.mutable;
+    }
+
+    pub fn cycle_symmetry(&mut self) {
+        if let Some(i) = self.rule_cursor.rule() {
+            let rule = &mut self.lvl.auto.rules[i];
+            rule.symmetry = rule.symmetry.next();
+        }
+    }
+
+    // Random updates get a fresh seed, which is then saved with the level.
+    pub fn cycle_update(&mut self) {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        self.lvl.auto.update = self.lvl.auto.update.next(seed);
+    }
+
+    // Turns the rule under the cursor into a counting rule or back.
+    pub fn toggle_count(&mut self) {
+        if let Some(i) = self.rule_cursor.rule() {
+            let rule = &mut self.lvl.auto.rules[i];
+            rule.count = match rule.count {
+                Some(_) => None,
+                None => Some(Count {
+                    of: 0x20,
+                    counts: vec![],
+                }),
+            };
+        }
+    }
+
+    // Starts editing the character class of the symbol under the rule
+    // cursor, declaring it if there is none yet. Symbols the validator would
+    // reject as classes are refused right away.
+    pub fn edit_class(&mut self) {
+        let (i, cell) = match (self.rule_cursor.rule(), self.rule_cursor.cell(&self.lvl)) {
+            (Some(i), Some(cell)) => (i, cell),
+            _ => return,
+        };
+        let symbol = self.lvl.auto.rules[i].pattern[cell];
+        let in_field = |f: &Field| f.data.iter().flatten().any(|b| *b == symbol);
+        if symbol == 0x5f || Rule::is_var(symbol) {
+            self.message = "the wildcard and variables cannot be classes".to_string();
+            return;
+        }
+        if in_field(&self.lvl.start) || in_field(&self.lvl.goal) {
+            self.message = format!(
+                "'{}' is a field cell, so it cannot be a class",
+                symbol as char
+            );
+            return;
+        }
+        let classes = &mut self.lvl.auto.classes;
+        if !classes.iter().any(|c| c.symbol == symbol) {
+            classes.push(CharClass {
+                symbol,
+                chars: vec![],
+                negate: false,
+            });
+        }
+        self.pending_class = Some(symbol);
+    }
+
+    // While a class is edited, keys toggle whether it contains them, ctrl+o
+    // negates it and enter finishes. A class left empty is removed again.
+    fn class_event(&mut self, symbol: u8, key: Key) {
+        let classes = &mut self.lvl.auto.classes;
+        let i = match classes.iter().position(|c| c.symbol == symbol) {
+            Some(i) => i,
+            None => {
+                self.pending_class = None;
+                return;
+            }
+        };
+        let class = &mut classes[i];
+        match key {
+            Key::Ctrl('o') => class.negate = !class.negate,
+            Key::Char('\n') | Key::Esc => {
+                if class.chars.is_empty() && !class.negate {
+                    classes.remove(i);
+                }
+                self.pending_class = None;
+            }
+            Key::Char(c) if c.is_ascii_graphic() || c == ' ' => {
+                match class.chars.iter().position(|b| *b == c as u8) {
+                    Some(j) => {
+                        class.chars.remove(j);
+                    }
+                    None => {
+                        class.chars.push(c as u8);
+                        class.chars.sort();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn toggle_cell_lock(&mut self) {
+        if let CursorPos::Pattern { i, .. } = self.rule_cursor {
+            if let Some(cell) = self.rule_cursor.cell(&self.lvl) {
+                self.lvl.auto.rules[i as usize].toggle_cell_lock(cell);
+            }
+        }
+    }
+
+    // Switches to the next neighborhood. The patterns no longer fit, so all
+    // rules start over empty.
+    pub fn cycle_neighborhood(&mut self) {
+        let all = Neighborhood::ALL;
+        let cur = all.iter().position(|nb| *nb == self.lvl.auto.neighborhood);
+        let nb = all[(cur.unwrap_or(0) + 1) % all.len()];
+        let n = self.lvl.auto.rules.len();
+        self.lvl.auto.neighborhood = nb;
+        self.lvl.auto.rules = vec![Self::empty_rule(nb); n];
+        self.rule_cursor = CursorPos::start(&self.lvl);
+    }
+
+    pub fn cycle_policy(&mut self) {
+        let all = MatchPolicy::ALL;
+        let cur = all.iter().position(|p| *p == self.lvl.auto.policy);
+        self.lvl.auto.policy = all[(cur.unwrap_or(0) + 1) % all.len()];
+    }
+
+    pub fn set_char(&mut self, chr: char) {
+        let mut buf = [0; 4];
+        let chr = chr.encode_utf8(&mut buf).as_bytes()[0];
+        let (x, y) = (self.x, self.y);
+        if let Some(field) = self.field_mut() {
+            field.set(x, y, chr);
+            self.x = (x + 1).min(field.w - 1);
+            return;
+        }
+        match self.rule_cursor {
+            CursorPos::Pattern { i, .. } => {
+                let cell = self.rule_cursor.cell(&self.lvl).unwrap();
+                self.lvl.auto.rules[i as usize].pattern[cell] = chr
+            }
+            CursorPos::Replace { i } => self.lvl.auto.rules[i as usize].replace = chr,
+            CursorPos::State { .. } => {}
+        }
+    }
+
+    fn move_cursor(&mut self, key: Key) {
+        // Text is only ever edited at its end.
+        if self.text_mut().is_some() {
+            return;
+        }
+        if let Focus::Rules = self.focus {
+            let cursor = match key {
+                Key::Up => self.rule_cursor.up(&self.lvl),
+                Key::Down => self.rule_cursor.down(&self.lvl),
+                Key::Left => self.rule_cursor.left(&self.lvl),
+                _ => self.rule_cursor.right(&self.lvl),
+            };
+            // The rule box has no state row to move into.
+            if !cursor.in_state() {
+                self.rule_cursor = cursor;
+            }
+            return;
+        }
+        let (w, h) = (self.lvl.start.w, self.lvl.start.h);
+        match key {
+            Key::Up => self.y = self.y.saturating_sub(1),
+            Key::Down => self.y = (self.y + 1).min(h - 1),
+            Key::Left => self.x = self.x.saturating_sub(1),
+            _ => self.x = (self.x + 1).min(w - 1),
+        }
+    }
+
+    pub fn event(&mut self, event: Event) -> Option<GameState> {
+        let (w, h) = (self.lvl.start.w, self.lvl.start.h);
+        let rules = self.lvl.auto.rules.len();
+        self.message.clear();
+        if let (Some(symbol), Event::Key(key)) = (self.pending_class, &event) {
+            self.class_event(symbol, *key);
+            return None;
+        }
+        if let (Focus::Rules, Event::Key(key)) = (&self.focus, &event) {
+            let (lvl, pending) = (&mut self.lvl, &mut self.pending_distinct);
+            if PlayingUI::distinct_event(lvl, &self.rule_cursor, pending, *key)
+                || self.pending_distinct.is_some()
+                || PlayingUI::count_event(
+                    &mut self.lvl,
+                    &self.rule_cursor,
+                    &mut self.pending_count,
+                    *key,
+                )
+            {
+                return None;
+            }
+        }
+        match event {
+            Event::Key(Key::Esc) => return Some(GameState::ToMenu),
+            Event::Key(Key::Char('\t')) => {
+                self.focus = match self.focus {
+                    Focus::Start => Focus::Goal,
+                    Focus::Goal => Focus::Rules,
+                    Focus::Rules => Focus::Title,
+                    Focus::Title => Focus::Description,
+                    Focus::Description => Focus::Start,
+                }
+            }
+            Event::Key(Key::Ctrl('s')) => self.save(),
+            Event::Key(Key::Ctrl('l')) => self.toggle_lock(),
+            Event::Key(Key::Ctrl('k')) => self.toggle_cell_lock(),
+            Event::Key(Key::Ctrl('n')) => self.cycle_neighborhood(),
+            Event::Key(Key::Ctrl('p')) => self.cycle_policy(),
+            Event::Key(Key::Ctrl('r')) => self.cycle_symmetry(),
+            Event::Key(Key::Ctrl('t')) => self.toggle_count(),
+            Event::Key(Key::Ctrl('o')) => self.edit_class(),
+            Event::Key(Key::Ctrl('u')) => self.cycle_update(),
+            Event::Key(Key::Ctrl('a')) => {
+                self.lvl.auto.allow_symmetry = !self.lvl.auto.allow_symmetry
+            }
+            Event::Key(Key::Ctrl('b')) => self.lvl.auto.boundary = self.lvl.auto.boundary.next(),
+            Event::Key(Key::CtrlRight) => match self.focus {
+                Focus::Rules => self.resize_rules(rules + 1),
+                _ => self.resize(w + 1, h),
+            },
+            Event::Key(Key::CtrlLeft) => match self.focus {
+                Focus::Rules => self.resize_rules(rules - 1),
+                _ => self.resize(w - 1, h),
+            },
+            Event::Key(Key::CtrlDown) => self.resize(w, h + 1),
+            Event::Key(Key::CtrlUp) => self.resize(w, h - 1),
+            Event::Key(key @ Key::Up)
+            | Event::Key(key @ Key::Down)
+            | Event::Key(key @ Key::Left)
+            | Event::Key(key @ Key::Right) => self.move_cursor(key),
+            Event::Key(Key::Backspace) if self.text_mut().is_some() => {
+                self.text_mut().unwrap().pop();
+            }
+            Event::Key(Key::Char('\n')) if matches!(self.focus, Focus::Description) => {
+                self.lvl.description.push('\n')
+            }
+            Event::Key(Key::Char(c)) if self.text_mut().is_some() && !c.is_control() => {
+                self.text_mut().unwrap().push(c)
+            }
+            Event::Key(Key::Backspace) => {
+                self.move_cursor(Key::Left);
+                let (x, y) = (self.x, self.y);
+                match self.field_mut() {
+                    Some(field) => field.set(x, y, 0x20),
+                    None => self.set_char(' '),
+                }
+            }
+            Event::Key(Key::Char(c)) if c.is_ascii_graphic() || c == ' ' => self.set_char(c),
+            _ => {}
+        }
+        return None;
+    }
+
+    pub fn draw_help(&self, out: &mut Out, x: u16, y: u16) {
+        let focus = match self.focus {
+            Focus::Start => "start",
+            Focus::Goal => "goal",
+            Focus::Rules => "rules",
+            Focus::Title => "title",
+            Focus::Description => "description",
+        };
+        let locked = self
+            .lvl
+            .auto
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| !r.mutable)
+            .map(|(i, _)| (i + 1).to_string())
+            .collect::<Vec<_>>();
+        // Symmetry of the rule under the cursor.
+        let symmetry = match self.rule_cursor.rule() {
+            Some(i) => self.lvl.auto.rules[i].symmetry,
+            None => Symmetry::None,
+        };
+        let status = match self.pending_class {
+            Some(symbol) => {
+                let class = self.lvl.auto.classes.iter().find(|c| c.symbol == symbol);
+                format!(
+                    "class {}  keys: add/remove  ctrl+o: negate  enter: done",
+                    class.map_or(String::new(), |c| c.describe())
+                )
+            }
+            None => self.message.clone(),
+        };
+        let lines = [
+            format!("Editing {} ({}): {}", self.path(), focus, status),
+            format!(
+                "neighborhood: {}  edges: {}  matching: {}  updates: {}",
+                self.lvl.auto.neighborhood.name(),
+                self.lvl.auto.boundary.name(),
+                self.lvl.auto.policy.name(),
+                self.lvl.auto.update.name()
+            ),
+            format!(
+                "locked rules: {}  symmetry: {} (players: {})",
+                locked.join(" "),
+                symmetry.name(),
+                if self.lvl.auto.allow_symmetry {
+                    "allowed"
+                } else {
+                    "forbidden"
+                }
+            ),
+            "tab: start/goal/rules/title/description  ctrl+arrows: resize field or rule slots"
+                .to_string(),
+            "ctrl+l: lock rule  ctrl+k: lock pattern cell  ctrl+n: neighborhood".to_string(),
+            "ctrl+b: edges  ctrl+p: matching  ctrl+r: rule symmetry  ctrl+a: allow symmetry"
+                .to_string(),
+            "ctrl+t: counting rule  ctrl+o: class of pattern symbol  ctrl+u: updates".to_string(),
+            "ctrl+s: save  esc: menu".to_string(),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            write!(out, "{}{}", termion::cursor::Goto(x, y + i as u16), line).unwrap();
+        }
+    }
+
+    pub fn draw(&self, out: &mut Out) {
+        write!(out, "{}", termion::clear::All).unwrap();
+        let (w, _h) = termion::terminal_size().unwrap();
+        let state_len = self.lvl.start.w * 3 + 8;
+        if w < PlayingUI::rules_len(&self.lvl) as u16 || w < state_len as u16 {
+            return PlayingUI::draw_terminal_to_small(out);
+        }
+        let (rule_x, rule_y) = PlayingUI::rules_start(&self.lvl);
+        let state_x = (w - state_len as u16) / 2;
+        let state_y = PlayingUI::state_start(&self.lvl);
+        let field_w = self.lvl.start.w;
+        PlayingUI::draw_level_description(out, &self.lvl, self.lvl.hints.len());
+        PlayingUI::draw_rule_box(out, rule_x, rule_y, &self.lvl.auto);
+        PlayingUI::draw_state_box(out, state_x, state_y, field_w, self.lvl.start.h);
+        PlayingUI::draw_rules(out, &self.lvl.auto, rule_x as usize, rule_y as usize);
+        PlayingUI::draw_replacements(out, &self.lvl.auto, rule_x as usize, rule_y as usize);
+        // The middle box previews one step of the rules on the start field.
+        let preview = self.lvl.auto.step(&self.lvl.start, 0);
+        let (sx, sy) = (state_x as usize, state_y as usize);
+        PlayingUI::draw_field(out, &self.lvl.start, sx, sy);
+        PlayingUI::draw_field(out, &preview, sx + field_w + 3, sy);
+        PlayingUI::draw_field(out, &self.lvl.goal, sx + 2 * field_w + 6, sy);
+        self.draw_help(out, state_x, state_y + 3 + self.lvl.start.h as u16);
+        match self.focus {
+            Focus::Start => write!(
+                out,
+                "{}",
+                termion::cursor::Goto(state_x + 1 + self.x as u16, state_y + 1 + self.y as u16)
+            )
+            .unwrap(),
+            Focus::Goal => write!(
+                out,
+                "{}",
+                termion::cursor::Goto(
+                    state_x + 1 + (2 * field_w + 6 + self.x) as u16,
+                    state_y + 1 + self.y as u16
+                )
+            )
+            .unwrap(),
+            Focus::Rules => {
+                PlayingUI::draw_rule_details(
+                    out,
+                    &self.lvl,
+                    &self.rule_cursor,
+                    self.pending_distinct,
+                    &self.pending_count,
+                );
+                PlayingUI::draw_cursor(out, &self.rule_cursor, &self.lvl)
+            }
+            Focus::Title | Focus::Description => {
+                let (x, y) = self.text_cursor();
+                write!(out, "{}", termion::cursor::Goto(x, y)).unwrap();
+            }
+        }
+        out.flush().unwrap();
+    }
+}
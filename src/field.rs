@@ -1,13 +1,86 @@
+use crate::boundary::Boundary;
+use crate::grid::{self, GridRepr, GridVisitor};
+use crate::neighborhood::Neighborhood;
 use itertools::Itertools;
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{Deserializer, Error, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
 
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Field {
     pub w: usize,
     pub h: usize,
     pub data: Vec<Vec<u8>>,
 }
 
+#[derive(Deserialize)]
+struct NumericField {
+    w: usize,
+    h: usize,
+    data: Vec<Vec<u8>>,
+}
+
+// Besides the grid forms a field may still be written the old way, as
+// `(w: ..., h: ..., data: [[...], ...])`.
+struct FieldVisitor;
+
+impl<'de> Visitor<'de> for FieldVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return GridVisitor.expecting(f);
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Field, A::Error> {
+        let NumericField { w, h, data } =
+            NumericField::deserialize(MapAccessDeserializer::new(map))?;
+        return Ok(Field { w, h, data });
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Field, E> {
+        return Field::from_grid(GridVisitor.visit_str(v)?);
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Field, A::Error> {
+        return Field::from_grid(GridVisitor.visit_seq(seq)?);
+    }
+}
+
+impl Serialize for Field {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let rows = self.data.iter().map(|row| grid::to_string(row));
+        return s.collect_seq(rows);
+    }
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        return d.deserialize_any(FieldVisitor);
+    }
+}
+
+// One line per row, the way fields are written in level files.
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.data.iter() {
+            writeln!(f, "{}", grid::to_string(row))?;
+        }
+        return Ok(());
+    }
+}
+
 impl Field {
+    fn from_grid<E: Error>(grid: GridRepr) -> Result<Self, E> {
+        let data = grid.rows()?;
+        let w = data.first().map_or(0, |row| row.len());
+        return Ok(Self {
+            w,
+            h: data.len(),
+            data,
+        });
+    }
+
     //pub fn new(w: usize, h: usize) -> Self {
     //    assert! { h > 4 && w > 4 };
     //    let mut data = vec![];
@@ -17,6 +90,16 @@ impl Field {
     //    Self { data, w, h }
     //}
 
+    // Grows or shrinks the field, filling new cells with spaces.
+    pub fn resize(&mut self, w: usize, h: usize) {
+        self.data.resize(h, vec![]);
+        for line in self.data.iter_mut() {
+            line.resize(w, 0x20);
+        }
+        self.w = w;
+        self.h = h;
+    }
+
     pub fn coords(&self) -> itertools::Product<std::ops::Range<usize>, std::ops::Range<usize>> {
         return (0..self.w).cartesian_product(0..self.h);
     }
@@ -27,24 +110,54 @@ impl Field {
         self.data[y][x] = val;
     }
 
-    pub fn get(&self, x: isize, y: isize) -> u8 {
-        if x < 0 || y < 0 {
-            return 0x20;
-        }
-        *self
-            .data
-            .get(y as usize)
-            .and_then(|w| w.get(x as usize))
-            .unwrap_or(&0x20)
+    pub fn get(&self, x: isize, y: isize, boundary: Boundary) -> u8 {
+        // `data` is public, so rows may be shorter than `w` or missing.
+        // Cells that are not there read like cells outside the field.
+        let cell = match (boundary.coord(x, self.w), boundary.coord(y, self.h)) {
+            (Some(x), Some(y)) => self.data.get(y).and_then(|row| row.get(x)),
+            _ => None,
+        };
+        return cell.copied().unwrap_or_else(|| boundary.fill());
     }
 
-    pub fn neighborhood(&self, x: usize, y: usize) -> Vec<u8> {
+    pub fn neighborhood(
+        &self,
+        x: usize,
+        y: usize,
+        nb: Neighborhood,
+        boundary: Boundary,
+    ) -> Vec<u8> {
+        let (rx, ry) = nb.radius();
         let mut res = vec![];
-        for yo in -1..=1 {
-            for xo in -1..=1 {
-                res.push(self.get((x as isize) + xo, (y as isize) + yo));
+        for yo in -ry..=ry {
+            for xo in -rx..=rx {
+                if nb.contains(xo, yo) {
+                    res.push(self.get((x as isize) + xo, (y as isize) + yo, boundary));
+                }
             }
         }
         return res;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ragged_field_reads_missing_cells_as_fill() {
+        let f = Field {
+            w: 3,
+            h: 3,
+            data: vec![b"abc".to_vec(), b"d".to_vec()],
+        };
+        let boundary = Boundary::Constant(b'#');
+        assert_eq!(f.get(0, 1, boundary), b'd');
+        assert_eq!(f.get(2, 1, boundary), b'#');
+        assert_eq!(f.get(1, 2, boundary), b'#');
+        assert_eq!(
+            f.neighborhood(1, 1, Neighborhood::VonNeumann, boundary),
+            b"bd###"
+        );
+    }
+}
@@ -1,32 +1,117 @@
+use crate::campaign;
 use crate::game_state::GameState;
-use crate::level::Level;
+use crate::menu_ui::MenuUI;
 use crate::playing_ui::{Out, PlayingUI};
+use crate::progress::Progress;
+use rulez::Level;
 use termion::event::Event;
 
 pub struct Game {
-    pub levels: Vec<Level>,
+    // Every level with the source its progress is saved under.
+    pub levels: Vec<(String, Level)>,
+    pub progress: Progress,
     pub out: Out,
     pub state: GameState,
     pub level: usize,
+    // Shown above the level list.
+    pub title: String,
+    // Where the editor saves new levels.
+    pub dir: String,
 }
 
 impl Game {
-    pub fn tick(&mut self) {
-        if let GameState::Success = self.state {
-            let lvl = self.levels[self.level].clone();
-            self.level += 1;
-            let ui = PlayingUI::new(lvl);
-            self.state = GameState::Play(ui);
+    pub fn new(
+        levels: Vec<(String, Level)>,
+        progress: Progress,
+        out: Out,
+        title: &str,
+        dir: &str,
+    ) -> Self {
+        let state = GameState::Menu(MenuUI::new(&levels, &progress, 0, title, dir));
+        return Self {
+            levels,
+            progress,
+            out,
+            state,
+            level: 0,
+            title: title.to_string(),
+            dir: dir.to_string(),
+        };
+    }
+
+    // Skips the menu and goes straight to the level at index `i`.
+    pub fn start(&mut self, i: usize) {
+        self.switch(GameState::Start(i));
+        self.state.draw(&mut self.out);
+    }
+
+    // Resolves the states that only the game can act on, since they need
+    // access to the list of levels.
+    fn switch(&mut self, state: GameState) {
+        if let GameState::Play(ui) = &self.state {
+            self.progress
+                .record_rules(&ui.source, ui.lvl.id, &ui.lvl.auto.rules);
+            if let GameState::Success(success) = &state {
+                self.progress
+                    .record_solved(&ui.source, success.id, success.steps);
+            }
+            // Losing progress is not worth interrupting the game for.
+            let _ = self.progress.save();
         }
+        self.state = match state {
+            GameState::Start(i) if i < self.levels.len() => {
+                self.level = i;
+                let (source, lvl) = self.levels[i].clone();
+                GameState::Play(Box::new(PlayingUI::new(source, lvl, &self.progress)))
+            }
+            GameState::Start(_) => GameState::Menu(MenuUI::new(
+                &self.levels,
+                &self.progress,
+                self.level,
+                &self.title,
+                &self.dir,
+            )),
+            GameState::ToMenu => GameState::Menu(MenuUI::new(
+                &self.levels,
+                &self.progress,
+                self.level,
+                &self.title,
+                &self.dir,
+            )),
+            GameState::Success(mut success) => {
+                success.level = self.level;
+                success.next = Some(self.level + 1).filter(|&i| i < self.levels.len());
+                success.all_solved = self
+                    .levels
+                    .iter()
+                    .all(|(source, lvl)| self.progress.is_solved(source, lvl.id));
+                GameState::Success(success)
+            }
+            state => state,
+        };
+    }
+
+    // Makes a level saved in the editor playable without a restart.
+    fn add_level(&mut self, path: &str, lvl: Level) {
+        let i = self.levels.partition_point(|(_, l)| l.id <= lvl.id);
+        self.levels.insert(i, (campaign::source(None, path), lvl));
+    }
+
+    pub fn tick(&mut self) {
         if let Some(newstate) = self.state.tick() {
-            self.state = newstate;
+            self.switch(newstate);
         }
         self.state.draw(&mut self.out);
     }
 
     pub fn event(&mut self, event: Event) {
         if let Some(state) = self.state.event(event) {
-            self.state = state;
+            self.switch(state);
+        }
+        if let GameState::Edit(editor) = &mut self.state {
+            if let Some((path, lvl)) = editor.saved.take() {
+                self.add_level(&path, lvl);
+            }
         }
         self.state.draw(&mut self.out);
     }
@@ -1,45 +1,60 @@
+use crate::editor_ui::EditorUI;
+use crate::menu_ui::MenuUI;
 use crate::playing_ui::Out;
 use crate::playing_ui::PlayingUI;
+use crate::success_ui::SuccessUI;
 use termion::event::Event;
 
 pub enum GameState {
     Quit,
-    Menu,
-    Play(PlayingUI),
-    Success,
+    Menu(MenuUI),
+    Play(Box<PlayingUI>),
+    Edit(Box<EditorUI>),
+    Success(SuccessUI),
+    Start(usize),
+    ToMenu,
 }
 
 impl GameState {
     pub fn tick(&mut self) -> Option<Self> {
         match self {
             Self::Quit => return None,
-            Self::Menu => return None,
+            Self::Menu(_) => return None,
             Self::Play(ui) => {
                 return if ui.update() {
-                    Some(Self::Success)
+                    Some(Self::Success(SuccessUI::new(ui)))
                 } else {
                     None
                 }
             }
-            Self::Success => return None,
+            Self::Edit(_) => return None,
+            Self::Success(_) => return None,
+            Self::Start(_) => return None,
+            Self::ToMenu => return None,
         }
     }
 
     pub fn event(&mut self, event: Event) -> Option<Self> {
         match self {
             Self::Quit => return None,
-            Self::Menu => return None,
+            Self::Menu(menu) => return menu.event(event),
             Self::Play(ui) => return ui.event(event),
-            Self::Success => return None,
+            Self::Edit(editor) => return editor.event(event),
+            Self::Success(success) => return success.event(event),
+            Self::Start(_) => return None,
+            Self::ToMenu => return None,
         }
     }
 
     pub fn draw(&mut self, out: &mut Out) {
         match self {
             Self::Quit => return,
-            Self::Menu => return,
+            Self::Menu(menu) => menu.draw(out),
             Self::Play(ui) => ui.draw(out),
-            Self::Success => return,
+            Self::Edit(editor) => editor.draw(out),
+            Self::Success(success) => success.draw(out),
+            Self::Start(_) => return,
+            Self::ToMenu => return,
         }
     }
 }
@@ -0,0 +1,366 @@
+// Readable serialization of byte grids: fields and rule patterns are written
+// as rows of the actual characters instead of lists of byte codes. The old
+// numeric form is still accepted when reading.
+use crate::neighborhood::Neighborhood;
+use serde::de::{Deserializer, Error, SeqAccess, Visitor};
+use serde::{Deserialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+
+// How a grid is written: a list of rows, a single multi-line string, or
+// for patterns the old list of byte codes. Told apart by the shape of the
+// input instead of an untagged enum, so that errors inside a grid reach the
+// user as they are instead of as "data did not match any variant".
+pub enum GridRepr {
+    Rows(Vec<String>),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+// A single entry of a list, either a row or a byte code.
+enum Entry {
+    Row(String),
+    Byte(u8),
+}
+
+struct EntryVisitor;
+
+impl<'de> Visitor<'de> for EntryVisitor {
+    type Value = Entry;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "a row string or a byte code");
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Entry, E> {
+        return Ok(Entry::Row(v.to_string()));
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Entry, E> {
+        return u8::try_from(v)
+            .map(Entry::Byte)
+            .map_err(|_| E::custom(format!("byte code {} is larger than 255", v)));
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Entry, E> {
+        return u64::try_from(v)
+            .map_err(|_| E::custom(format!("byte code {} is negative", v)))
+            .and_then(|v| self.visit_u64(v));
+    }
+}
+
+impl<'de> Deserialize<'de> for Entry {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        return d.deserialize_any(EntryVisitor);
+    }
+}
+
+pub struct GridVisitor;
+
+impl<'de> Visitor<'de> for GridVisitor {
+    type Value = GridRepr;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "a list of rows or a multi-line string");
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<GridRepr, E> {
+        return Ok(GridRepr::Text(v.to_string()));
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<GridRepr, A::Error> {
+        let mut rows = vec![];
+        let mut bytes = vec![];
+        while let Some(entry) = seq.next_element::<Entry>()? {
+            match entry {
+                Entry::Row(row) => rows.push(row),
+                Entry::Byte(b) => bytes.push(b),
+            }
+            if !rows.is_empty() && !bytes.is_empty() {
+                return Err(A::Error::custom("rows and byte codes cannot be mixed"));
+            }
+        }
+        if !bytes.is_empty() {
+            return Ok(GridRepr::Bytes(bytes));
+        }
+        return Ok(GridRepr::Rows(rows));
+    }
+}
+
+impl<'de> Deserialize<'de> for GridRepr {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        return d.deserialize_any(GridVisitor);
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ByteRepr {
+    Num(u8),
+    Char(char),
+}
+
+pub fn to_bytes<E: Error>(s: &str) -> Result<Vec<u8>, E> {
+    return s
+        .chars()
+        .map(|c| {
+            if (c as u32) < 0x100 {
+                Ok(c as u8)
+            } else {
+                Err(E::custom(format!(
+                    "'{}' does not fit into a single byte",
+                    c
+                )))
+            }
+        })
+        .collect();
+}
+
+pub fn to_string(bytes: &[u8]) -> String {
+    return bytes.iter().map(|b| *b as char).collect();
+}
+
+// Stands for the cells of a pattern's bounding box that are not part of its
+// neighborhood, like the corners of a von Neumann pattern. It does not fit
+// into a byte, so it cannot be mistaken for a cell.
+pub const OUTSIDE: char = '∙';
+
+impl GridRepr {
+    pub fn rows<E: Error>(self) -> Result<Vec<Vec<u8>>, E> {
+        return self.lines()?.iter().map(|row| to_bytes(row)).collect();
+    }
+
+    // A text grid has one row per line. Blank lines around it are only there
+    // to line up the rows in the file, so they are dropped. All rows must
+    // have the same length.
+    fn lines<E: Error>(self) -> Result<Vec<String>, E> {
+        let rows: Vec<String> = match self {
+            GridRepr::Rows(rows) => rows,
+            GridRepr::Bytes(_) => return Err(E::custom("expected rows, found byte codes")),
+            GridRepr::Text(text) => {
+                let mut lines = text.split('\n').collect::<Vec<_>>();
+                if lines.first().is_some_and(|l| l.trim().is_empty()) {
+                    lines.remove(0);
+                }
+                if lines.last().is_some_and(|l| l.trim().is_empty()) {
+                    lines.pop();
+                }
+                lines.iter().map(|l| l.to_string()).collect()
+            }
+        };
+        let len = |row: &String| row.chars().count();
+        for (i, row) in rows.iter().enumerate().skip(1) {
+            if len(row) != len(&rows[0]) {
+                return Err(E::custom(format!(
+                    "row {} has {} cells but row 1 has {}",
+                    i + 1,
+                    len(row),
+                    len(&rows[0])
+                )));
+            }
+        }
+        return Ok(rows);
+    }
+}
+
+// Patterns are written in the bounding box of their neighborhood, which
+// follows from the number of cells, with `OUTSIDE` filling the gaps. A
+// pattern that fits no neighborhood is written as a single row.
+pub fn serialize_pattern<S: Serializer>(pattern: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    let nb = Neighborhood::ALL
+        .iter()
+        .find(|nb| nb.cells() == pattern.len());
+    let nb = match nb {
+        Some(nb) => nb,
+        None => return s.collect_seq(std::iter::once(to_string(pattern))),
+    };
+    let rows = (0..nb.height()).map(|y| {
+        return (0..nb.width())
+            .map(|x| nb.index(x, y).map_or(OUTSIDE, |i| pattern[i] as char))
+            .collect::<String>();
+    });
+    return s.collect_seq(rows);
+}
+
+pub fn deserialize_pattern<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+    match GridRepr::deserialize(d)? {
+        GridRepr::Bytes(pattern) => return Ok(pattern),
+        grid => {
+            let cells = grid.lines()?.concat().replace(OUTSIDE, "");
+            return to_bytes(&cells);
+        }
+    }
+}
+
+pub fn serialize_text<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    return s.serialize_str(&to_string(bytes));
+}
+
+pub fn deserialize_text<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+    return to_bytes(&String::deserialize(d)?);
+}
+
+pub fn serialize_byte<S: Serializer>(b: &u8, s: S) -> Result<S::Ok, S::Error> {
+    return s.serialize_char(*b as char);
+}
+
+pub fn deserialize_byte<'de, D: Deserializer<'de>>(d: D) -> Result<u8, D::Error> {
+    match ByteRepr::deserialize(d)? {
+        ByteRepr::Num(b) => return Ok(b),
+        ByteRepr::Char(c) => return Ok(to_bytes::<D::Error>(&c.to_string())?[0]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::field::Field;
+    use crate::level::Level;
+    use crate::rule::Rule;
+
+    fn field(contents: &str) -> Result<Field, ron::error::SpannedError> {
+        return ron::de::from_str(contents);
+    }
+
+    fn rule(pattern: &str) -> Result<Rule, ron::error::SpannedError> {
+        let contents = format!("(pattern: {}, replace: 'x', mutable: true)", pattern);
+        return ron::de::from_str(&contents);
+    }
+
+    fn error(e: ron::error::SpannedError) -> String {
+        return e.code.to_string();
+    }
+
+    #[test]
+    fn field_rows() {
+        let f = field(r#"["ab ", " cd"]"#).unwrap();
+        assert_eq!((f.w, f.h), (3, 2));
+        assert_eq!(f.data, vec![b"ab ".to_vec(), b" cd".to_vec()]);
+    }
+
+    #[test]
+    fn field_text() {
+        let f = field("\"\n ab\ncd \n\"").unwrap();
+        assert_eq!((f.w, f.h), (3, 2));
+        assert_eq!(f.data, vec![b" ab".to_vec(), b"cd ".to_vec()]);
+    }
+
+    #[test]
+    fn field_numeric() {
+        let f = field("(w: 2, h: 1, data: [[32, 120]])").unwrap();
+        assert_eq!((f.w, f.h), (2, 1));
+        assert_eq!(f.data, vec![b" x".to_vec()]);
+    }
+
+    #[test]
+    fn field_high_bytes() {
+        let f = field(r#"["\u{ff}"]"#).unwrap();
+        assert_eq!(f.data, vec![vec![0xff]]);
+        let e = field(r#"["★"]"#).err().unwrap();
+        assert_eq!(error(e), "'★' does not fit into a single byte");
+    }
+
+    #[test]
+    fn ragged_rows() {
+        let e = field(r#"["abc", "ab"]"#).err().unwrap();
+        assert_eq!(error(e), "row 2 has 2 cells but row 1 has 3");
+        let e = field("\"abc\nab\"").err().unwrap();
+        assert_eq!(error(e), "row 2 has 2 cells but row 1 has 3");
+    }
+
+    #[test]
+    fn field_is_not_byte_codes() {
+        let e = field("[32, 120]").err().unwrap();
+        assert_eq!(error(e), "expected rows, found byte codes");
+    }
+
+    #[test]
+    fn pattern_rows_and_text() {
+        let rows = rule(r#"[" x ", "___", "A B"]"#).unwrap();
+        assert_eq!(rows.pattern, b" x ___A B".to_vec());
+        let text = rule("\" x \n___\nA B\"").unwrap();
+        assert_eq!(text.pattern, rows.pattern);
+    }
+
+    #[test]
+    fn pattern_byte_codes() {
+        let r = rule("[32, 120, 32, 95, 95, 95, 65, 32, 66]").unwrap();
+        assert_eq!(r.pattern, b" x ___A B".to_vec());
+    }
+
+    #[test]
+    fn bad_entries() {
+        let e = rule(r#"["abc", 32]"#).err().unwrap();
+        assert_eq!(error(e), "rows and byte codes cannot be mixed");
+        let e = rule("[300]").err().unwrap();
+        assert_eq!(error(e), "byte code 300 is larger than 255");
+        let e = rule("[-1]").err().unwrap();
+        assert_eq!(error(e), "byte code -1 is negative");
+    }
+
+    #[test]
+    fn replace_as_char_or_byte_code() {
+        let contents = "(pattern: [\"   \", \"   \", \"   \"], replace: 120, mutable: true)";
+        let r: Rule = ron::de::from_str(contents).unwrap();
+        assert_eq!(r.replace, b'x');
+    }
+
+    fn write(rule: &Rule) -> String {
+        return ron::ser::to_string(rule).unwrap();
+    }
+
+    #[test]
+    fn von_neumann_pattern_keeps_its_shape() {
+        let r = Rule::new(b"ab_cd".to_vec(), b'y');
+        let written = write(&r);
+        assert!(written.starts_with(r#"(pattern:["∙a∙","b_c","∙d∙"]"#));
+        let read: Rule = ron::de::from_str(&written).unwrap();
+        assert_eq!(read.pattern, r.pattern);
+        // The single row it used to be written as still reads.
+        assert_eq!(rule(r#"["ab_cd"]"#).unwrap().pattern, r.pattern);
+    }
+
+    #[test]
+    fn pattern_shapes() {
+        let line = Rule::new(b"abc".to_vec(), b'y');
+        assert!(write(&line).starts_with(r#"(pattern:["abc"]"#));
+        let moore2 = Rule::new((b'a'..b'a' + 25).collect(), b'y');
+        assert!(write(&moore2).starts_with(r#"(pattern:["abcde","fghij","klmno","#));
+        let odd = Rule::new(b"abcd".to_vec(), b'y');
+        assert!(write(&odd).starts_with(r#"(pattern:["abcd"]"#));
+    }
+
+    #[test]
+    fn outside_is_not_a_field_cell() {
+        let e = field(r#"["∙"]"#).err().unwrap();
+        assert_eq!(error(e), "'∙' does not fit into a single byte");
+    }
+
+    // What `convert` does: read a level in any form, write it pretty.
+    fn convert(contents: &str) -> String {
+        let lvl: Level = ron::de::from_str(contents).unwrap();
+        let pretty = ron::ser::PrettyConfig::default();
+        return ron::ser::to_string_pretty(&lvl, pretty).unwrap();
+    }
+
+    #[test]
+    fn convert_round_trip() {
+        let old = "(
+            start: (w: 3, h: 2, data: [[32, 120, 32], [32, 32, 32]]),
+            goal: (w: 3, h: 2, data: [[32, 120, 32], [32, 120, 32]]),
+            auto: (rules: [(
+                pattern: [95, 120, 95, 95, 32, 95, 95, 95, 95],
+                replace: 120,
+                mutable: true,
+            )]),
+            id: 1,
+        )";
+        let converted = convert(old);
+        assert!(converted.contains(r#""_x_","#));
+        assert!(converted.contains("replace: 'x'"));
+        let lvl: Level = ron::de::from_str(&converted).unwrap();
+        let before: Level = ron::de::from_str(old).unwrap();
+        assert!(lvl.start == before.start && lvl.goal == before.goal);
+        assert_eq!(lvl.auto.rules[0].pattern, before.auto.rules[0].pattern);
+        assert_eq!(convert(&converted), converted);
+    }
+}
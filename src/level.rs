@@ -3,13 +3,47 @@ use crate::field::Field;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Level {
+    // What the player is told about the level. All of it is optional.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    // From 1 to `MAX_DIFFICULTY` stars, 0 if unrated.
+    #[serde(default, skip_serializing_if = "is_unrated")]
+    pub difficulty: u8,
+    // Revealed one at a time when the player asks for them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<String>,
     pub start: Field,
     pub goal: Field,
     pub auto: Automaton,
     pub id: usize,
 }
 
+pub const MAX_DIFFICULTY: u8 = 5;
+
+fn is_unrated(difficulty: &u8) -> bool {
+    return *difficulty == 0;
+}
+
 impl Level {
+    // Like `Level 3: Moving on`, or just `Level 3` without a title.
+    pub fn name(&self) -> String {
+        if self.title.is_empty() {
+            return format!("Level {}", self.id);
+        }
+        return format!("Level {}: {}", self.id, self.title);
+    }
+
+    // Like `★★☆☆☆`, empty if unrated.
+    pub fn stars(&self) -> String {
+        if self.difficulty == 0 {
+            return String::new();
+        }
+        let filled = self.difficulty.min(MAX_DIFFICULTY) as usize;
+        return "★".repeat(filled) + &"☆".repeat(MAX_DIFFICULTY as usize - filled);
+    }
+
     //pub fn new(start: Field, goal: Field) -> Self {
     //    return Self {
     //        start,
@@ -0,0 +1,38 @@
+// The simulation core of rulez: fields, rules, automata, levels and the
+// tools that work on them. Nothing in here touches the terminal, the game in
+// main.rs is built on top of it.
+#![allow(clippy::needless_return)]
+
+extern crate itertools;
+#[macro_use]
+extern crate serde_derive;
+extern crate ron;
+
+pub mod automaton;
+pub mod boundary;
+pub mod cycle;
+pub mod field;
+mod grid;
+pub mod level;
+pub mod neighborhood;
+pub mod pack;
+pub mod pattern;
+pub mod rule;
+pub mod solver;
+pub mod symmetry;
+pub mod update;
+pub mod validate;
+
+pub use automaton::{Automaton, Conflict, Firing, MatchPolicy, Trace};
+pub use boundary::Boundary;
+pub use cycle::{CycleDetector, Stuck};
+pub use field::Field;
+pub use level::Level;
+pub use neighborhood::Neighborhood;
+pub use pack::Pack;
+pub use pattern::{CharClass, Count, Distinct};
+pub use rule::Rule;
+pub use solver::Solver;
+pub use symmetry::Symmetry;
+pub use update::UpdateMode;
+pub use validate::{LevelError, LevelErrorKind};
@@ -1,38 +1,41 @@
-extern crate itertools;
+#![allow(clippy::needless_return)]
+
+extern crate rulez;
 extern crate termion;
 #[macro_use]
 extern crate serde_derive;
-extern crate glob;
 extern crate ron;
 
-mod automaton;
+mod campaign;
+mod cli;
 mod cursor_pos;
-mod field;
+mod editor_ui;
 mod game;
 mod game_state;
-mod level;
+mod menu_ui;
 mod playing_ui;
-mod rule;
+mod progress;
+mod success_ui;
 
+use cli::{Command, Options, USAGE};
 use game::Game;
 use game_state::GameState;
-use glob::glob;
-use level::Level;
-use playing_ui::PlayingUI;
-use std::fs::File;
-use std::io::prelude::*;
+use progress::Progress;
+use rulez::validate;
+use rulez::{CycleDetector, Level, LevelError, LevelErrorKind, Solver};
+use std::fs;
 use std::io::{stdin, stdout};
+use std::path::Path;
 use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time;
 use termion::input::{MouseTerminal, TermRead};
 use termion::raw::IntoRawMode;
-use termion::screen::AlternateScreen;
+use termion::screen::IntoAlternateScreen;
 
-fn animation(game: Arc<RwLock<Game>>) {
+fn animation(game: Arc<RwLock<Game>>, speed: u64) {
     loop {
-        let ten_millis = time::Duration::from_millis(500);
-        thread::sleep(ten_millis);
+        thread::sleep(time::Duration::from_millis(speed));
         game.write().unwrap().tick();
         if let GameState::Quit = game.read().unwrap().state {
             break;
@@ -40,30 +43,261 @@ fn animation(game: Arc<RwLock<Game>>) {
     }
 }
 
-fn load_level(path: &str) -> Level {
-    let mut file = File::open(path).unwrap();
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-    return ron::de::from_str(&contents).unwrap();
+fn level_paths(dir: &str) -> Vec<String> {
+    match read_level_dir(dir) {
+        Ok(paths) => return paths,
+        Err(error) => {
+            report(&[error]);
+            std::process::exit(1);
+        }
+    }
 }
 
-fn main() {
-    let levels = glob("level/level*")
-        .unwrap()
-        .map(|entry| load_level(&entry.unwrap().into_os_string().into_string().unwrap()))
+// The files in `dir` whose name starts with "level", sorted by name.
+fn read_level_dir(dir: &str) -> Result<Vec<String>, LevelError> {
+    let io_error = |path: &str, msg: String| LevelError::new(path, LevelErrorKind::Io(msg));
+    if !Path::new(dir).is_dir() {
+        let msg = "no such level directory, use --levels <dir> to pick one";
+        return Err(io_error(dir, msg.to_string()));
+    }
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir).map_err(|e| io_error(dir, e.to_string()))? {
+        let entry = entry.map_err(|e| io_error(dir, e.to_string()))?;
+        if !entry.file_name().to_string_lossy().starts_with("level") {
+            continue;
+        }
+        match entry.path().into_os_string().into_string() {
+            Ok(path) => paths.push(path),
+            Err(path) => {
+                let msg = "file name is not valid UTF-8".to_string();
+                return Err(io_error(&path.to_string_lossy(), msg));
+            }
+        }
+    }
+    paths.sort();
+    return Ok(paths);
+}
+
+fn report(errors: &[LevelError]) {
+    for e in errors.iter() {
+        eprintln!("{}", e);
+    }
+}
+
+fn load_levels(dir: Option<&str>, paths: &[String]) -> Vec<(String, Level)> {
+    match validate::load_levels(dir, paths) {
+        Ok(levels) => return levels,
+        Err(errors) => {
+            report(&errors);
+            std::process::exit(1);
+        }
+    }
+}
+
+// The built-in levels combined with those in the level directory. The
+// default directory is only used if it exists, so the game can be started
+// from anywhere. Also returns where the levels came from, for errors about
+// the campaign as a whole.
+fn local_campaign(opts: &Options) -> (String, Vec<(String, Level)>) {
+    let dir = opts.levels_dir();
+    let builtin = if opts.no_builtin {
+        vec![]
+    } else {
+        campaign::builtin().unwrap_or_else(|errors| {
+            report(&errors);
+            std::process::exit(1);
+        })
+    };
+    let read_dir = opts.levels.is_some() || Path::new(dir).is_dir();
+    let paths = if read_dir { level_paths(dir) } else { vec![] };
+    let source = match (opts.no_builtin, read_dir) {
+        (true, _) => dir.to_string(),
+        (false, false) => campaign::BUILTIN.to_string(),
+        (false, true) => format!("{} + {}", campaign::BUILTIN, dir),
+    };
+    let levels = campaign::merge(builtin, load_levels(None, &paths));
+    let errors = validate::validate_ids(&source, &levels);
+    if !errors.is_empty() {
+        report(&errors);
+        std::process::exit(1);
+    }
+    return (source, levels);
+}
+
+// The title shown in the menu and the levels of the pack or of the campaign, ordered by id and
+// narrowed down to a single one by `--level`.
+fn campaign(opts: &Options) -> (String, Vec<(String, Level)>) {
+    let (source, title, levels) = match &opts.pack {
+        Some(path) => match validate::load_pack(path) {
+            Ok((pack, levels)) => (path.clone(), pack.about(), levels),
+            Err(errors) => {
+                report(&errors);
+                std::process::exit(1);
+            }
+        },
+        None => {
+            let (source, levels) = local_campaign(opts);
+            (source, String::new(), levels)
+        }
+    };
+    let title = if title.is_empty() {
+        "rulez".to_string()
+    } else {
+        title
+    };
+    let id = match opts.level {
+        Some(id) if opts.command != Command::Play => id,
+        _ => return (title, levels),
+    };
+    let levels = levels
+        .into_iter()
+        .filter(|(_, lvl)| lvl.id == id)
         .collect::<Vec<_>>();
-    let screen = AlternateScreen::from(stdout().into_raw_mode().unwrap());
-    let out = MouseTerminal::from(screen);
-    let game = Game {
-        levels,
-        out,
-        state: GameState::Success,
-        level: 0,
+    if levels.is_empty() {
+        eprintln!("{}: no level with id {}", source, id);
+        std::process::exit(1);
+    }
+    return (title, levels);
+}
+
+fn validate(opts: &Options) {
+    let count = if opts.paths.is_empty() {
+        campaign(opts).1.len()
+    } else {
+        load_levels(None, &opts.paths).len()
     };
+    println!("{} levels ok", count);
+}
+
+// Rewrites level files in the readable grid format.
+fn convert(opts: &Options) {
+    let paths = if opts.paths.is_empty() {
+        level_paths(opts.levels_dir())
+    } else {
+        opts.paths.clone()
+    };
+    for (path, lvl) in load_levels(None, &paths).iter() {
+        let pretty = ron::ser::PrettyConfig::default();
+        let contents = ron::ser::to_string_pretty(lvl, pretty).unwrap();
+        if let Err(e) = std::fs::write(path, contents + "\n") {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+        println!("converted {}", path);
+    }
+}
+
+// Plays a level without the terminal, e.g. to check solutions in CI. Prints
+// every field and exits with 0 only if the goal is reached.
+fn run(opts: &Options) {
+    let max_steps = opts.max_steps.unwrap_or(100);
+    let (_, mut lvl) = match (opts.paths.first(), opts.level) {
+        (Some(path), _) => load_levels(None, std::slice::from_ref(path)).remove(0),
+        (None, Some(_)) => campaign(opts).1.remove(0),
+        (None, None) => {
+            eprintln!("run needs a level file or --level <id>");
+            std::process::exit(2);
+        }
+    };
+    if let Some(rules_path) = &opts.rules {
+        let substituted = validate::load_rules(rules_path)
+            .map_err(|e| vec![e])
+            .and_then(|rules| validate::substitute_rules(rules_path, &lvl, rules));
+        match substituted {
+            Ok(res) => lvl = res,
+            Err(errors) => {
+                report(&errors);
+                std::process::exit(1);
+            }
+        }
+    }
+    let mut field = lvl.start.clone();
+    let mut cycles = CycleDetector::new(&field, lvl.auto.update.phases());
+    for step in 0..=max_steps {
+        println!("step {}:\n{}", step, field);
+        if field == lvl.goal {
+            println!("goal reached at step {}", step);
+            return;
+        }
+        if step == max_steps {
+            break;
+        }
+        let (next, conflicts) = lvl.auto.step_checked(&field, step);
+        for conflict in conflicts.iter() {
+            println!("{}", conflict);
+        }
+        field = next;
+        if let Some(stuck) = cycles.check(step + 1, &field) {
+            println!("step {}:\n{}", step + 1, field);
+            println!("stuck: {}", stuck);
+            std::process::exit(1);
+        }
+    }
+    println!("goal not reached after {} steps", max_steps);
+    std::process::exit(1);
+}
+
+fn solve(opts: &Options) {
+    let max_steps = opts.max_steps.unwrap_or(20);
+    let max_literals = opts.max_literals.unwrap_or(2);
+    let levels = if opts.paths.is_empty() {
+        campaign(opts).1
+    } else {
+        load_levels(None, &opts.paths)
+    };
+    let mut unsolved = 0;
+    for (path, lvl) in levels.iter() {
+        match Solver::new(lvl, max_steps, max_literals).solve() {
+            Some(auto) => {
+                let pretty = ron::ser::PrettyConfig::default().compact_arrays(true);
+                println!("// {} (level {})", path, lvl.id);
+                println!("{}", ron::ser::to_string_pretty(&auto, pretty).unwrap());
+            }
+            None => {
+                println!("// {} (level {}): no solution found", path, lvl.id);
+                unsolved += 1;
+            }
+        }
+    }
+    if unsolved > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn play(opts: &Options) {
+    let (title, levels) = campaign(opts);
+    let levels = levels
+        .into_iter()
+        .map(|(path, lvl)| (campaign::source(opts.pack.as_deref(), &path), lvl))
+        .collect::<Vec<_>>();
+    let start = opts
+        .level
+        .map(|id| match levels.iter().position(|(_, lvl)| lvl.id == id) {
+            Some(i) => i,
+            None => {
+                let source = opts.pack.as_deref().unwrap_or(opts.levels_dir());
+                eprintln!("{}: no level with id {}", source, id);
+                std::process::exit(1);
+            }
+        });
+    // Loaded before the alternate screen is entered, so problems with the save
+    // file stay readable on the normal screen.
+    let progress = Progress::load();
+    let screen = stdout()
+        .into_raw_mode()
+        .unwrap()
+        .into_alternate_screen()
+        .unwrap();
+    let out = MouseTerminal::from(screen);
+    let mut game = Game::new(levels, progress, out, &title, opts.levels_dir());
+    if let Some(i) = start {
+        game.start(i);
+    }
 
     let game = Arc::new(RwLock::new(game));
     let game2 = game.clone();
-    thread::spawn(|| animation(game2));
+    let speed = opts.speed.unwrap_or(500);
+    thread::spawn(move || animation(game2, speed));
 
     for c in stdin().events() {
         let evt = c.unwrap();
@@ -73,3 +307,25 @@ fn main() {
         }
     }
 }
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let opts = match Options::parse(&args) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("{}\nrun `automaton --help` for usage", e);
+            std::process::exit(2);
+        }
+    };
+    if opts.help {
+        println!("{}", USAGE);
+        return;
+    }
+    match opts.command {
+        Command::Play => play(&opts),
+        Command::Run => run(&opts),
+        Command::Validate => validate(&opts),
+        Command::Solve => solve(&opts),
+        Command::Convert => convert(&opts),
+    }
+}
@@ -0,0 +1,117 @@
+use crate::editor_ui::EditorUI;
+use crate::game_state::GameState;
+use crate::playing_ui::{Out, PlayingUI};
+use crate::progress::Progress;
+use rulez::Level;
+use std::io::Write;
+use termion::event::{Event, Key};
+
+pub struct MenuEntry {
+    pub id: usize,
+    pub name: String,
+    pub stars: String,
+    pub solved: bool,
+}
+
+pub struct MenuUI {
+    pub entries: Vec<MenuEntry>,
+    pub selected: usize,
+    pub title: String,
+    pub dir: String,
+}
+
+impl MenuUI {
+    pub fn new(
+        levels: &[(String, Level)],
+        progress: &Progress,
+        selected: usize,
+        title: &str,
+        dir: &str,
+    ) -> Self {
+        let entries = levels
+            .iter()
+            .map(|(source, lvl)| MenuEntry {
+                id: lvl.id,
+                name: lvl.name(),
+                stars: lvl.stars(),
+                solved: progress.is_solved(source, lvl.id),
+            })
+            .collect::<Vec<_>>();
+        return Self {
+            entries,
+            selected,
+            title: title.to_string(),
+            dir: dir.to_string(),
+        };
+    }
+
+    pub fn draw(&self, out: &mut Out) {
+        write!(out, "{}", termion::clear::All).unwrap();
+        let (w, h) = termion::terminal_size().unwrap();
+        let help = "up/down: select  enter: play  e: new level  q: quit";
+        // Stars line up after the longest level name.
+        let name_len = self
+            .entries
+            .iter()
+            .map(|e| e.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let lines = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let marker = if i == self.selected { ">" } else { " " };
+                let solved = if entry.solved { "x" } else { " " };
+                return format!(
+                    "{} [{}] {:<name_len$}  {}",
+                    marker, solved, entry.name, entry.stars
+                );
+            })
+            .collect::<Vec<_>>();
+        // The menu is as wide as its widest line.
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .chain([help.len(), self.title.chars().count()])
+            .max()
+            .unwrap_or(0);
+        // Title, list and help need at least one row for a level.
+        if (w as usize) < width || h < 7 {
+            return PlayingUI::draw_terminal_to_small(out);
+        }
+        // Scrolls so that the selected level is always on screen.
+        let rows = self.entries.len().min(h as usize - 6);
+        let first = (self.selected + 1).saturating_sub(rows.max(1));
+        let x = (w - width as u16) / 2;
+        write!(out, "{}{}", termion::cursor::Goto(x, 2), self.title).unwrap();
+        for (row, line) in lines.iter().skip(first).take(rows).enumerate() {
+            write!(out, "{}{}", termion::cursor::Goto(x, 4 + row as u16), line).unwrap();
+        }
+        let help_y = 5 + rows as u16;
+        write!(out, "{}{}", termion::cursor::Goto(x, help_y), help).unwrap();
+        write!(
+            out,
+            "{}",
+            termion::cursor::Goto(x, 4 + (self.selected - first) as u16)
+        )
+        .unwrap();
+        out.flush().unwrap();
+    }
+
+    pub fn event(&mut self, event: Event) -> Option<GameState> {
+        let len = self.entries.len();
+        match event {
+            Event::Key(Key::Char('q')) => return Some(GameState::Quit),
+            Event::Key(Key::Char('e')) => {
+                let id = self.entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+                return Some(GameState::Edit(Box::new(EditorUI::new(id, &self.dir))));
+            }
+            Event::Key(Key::Up) if len > 0 => self.selected = (len + self.selected - 1) % len,
+            Event::Key(Key::Down) if len > 0 => self.selected = (self.selected + 1) % len,
+            Event::Key(Key::Char('\n')) if len > 0 => return Some(GameState::Start(self.selected)),
+            _ => {}
+        }
+        return None;
+    }
+}
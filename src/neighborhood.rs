@@ -0,0 +1,139 @@
+// The cells around a position that a rule pattern looks at. Patterns list
+// the cells of the neighborhood's bounding box in row-major order, leaving
+// out the cells of the box that do not belong to the neighborhood.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Neighborhood {
+    // The 3x3 square around the cell.
+    #[default]
+    Moore,
+    // The cell and its four direct neighbors.
+    VonNeumann,
+    // The cell and its left and right neighbor.
+    Line,
+    // The 5x5 square around the cell.
+    Moore2,
+}
+
+impl Neighborhood {
+    pub const ALL: [Neighborhood; 4] = [
+        Neighborhood::Moore,
+        Neighborhood::VonNeumann,
+        Neighborhood::Line,
+        Neighborhood::Moore2,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Neighborhood::Moore => return "moore",
+            Neighborhood::VonNeumann => return "von neumann",
+            Neighborhood::Line => return "line",
+            Neighborhood::Moore2 => return "moore radius 2",
+        }
+    }
+
+    // Distance of the bounding box's edges from the center.
+    pub fn radius(&self) -> (isize, isize) {
+        match self {
+            Neighborhood::Moore | Neighborhood::VonNeumann => return (1, 1),
+            Neighborhood::Line => return (1, 0),
+            Neighborhood::Moore2 => return (2, 2),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        return (2 * self.radius().0 + 1) as usize;
+    }
+
+    pub fn height(&self) -> usize {
+        return (2 * self.radius().1 + 1) as usize;
+    }
+
+    // Whether the offset from the center is part of the neighborhood.
+    pub fn contains(&self, xo: isize, yo: isize) -> bool {
+        let (rx, ry) = self.radius();
+        if xo.abs() > rx || yo.abs() > ry {
+            return false;
+        }
+        match self {
+            Neighborhood::VonNeumann => return xo == 0 || yo == 0,
+            _ => return true,
+        }
+    }
+
+    pub fn cells(&self) -> usize {
+        return self.offsets().count();
+    }
+
+    // The offsets from the center in pattern order.
+    pub fn offsets(&self) -> impl Iterator<Item = (isize, isize)> + '_ {
+        let (rx, ry) = self.radius();
+        return (-ry..=ry)
+            .flat_map(move |yo| (-rx..=rx).map(move |xo| (xo, yo)))
+            .filter(move |(xo, yo)| self.contains(*xo, *yo));
+    }
+
+    // Maps a position inside the bounding box to its index in a pattern.
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        let (rx, ry) = self.radius();
+        let (xo, yo) = (x as isize - rx, y as isize - ry);
+        if !self.contains(xo, yo) {
+            return None;
+        }
+        return self.offsets().position(|o| o == (xo, yo));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::Boundary;
+    use crate::field::Field;
+
+    #[test]
+    fn cells() {
+        let cells = Neighborhood::ALL.iter().map(|nb| nb.cells());
+        assert_eq!(cells.collect::<Vec<_>>(), vec![9, 5, 3, 25]);
+    }
+
+    #[test]
+    fn center_is_in_the_middle_of_the_pattern() {
+        for nb in Neighborhood::ALL.iter() {
+            let center = nb.offsets().position(|o| o == (0, 0));
+            assert_eq!(center, Some(nb.cells() / 2), "{}", nb.name());
+        }
+    }
+
+    #[test]
+    fn von_neumann_offsets() {
+        let offsets = Neighborhood::VonNeumann.offsets().collect::<Vec<_>>();
+        assert_eq!(offsets, vec![(0, -1), (-1, 0), (0, 0), (1, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn index_skips_cells_outside() {
+        let nb = Neighborhood::VonNeumann;
+        assert_eq!(nb.index(0, 0), None);
+        assert_eq!(nb.index(1, 0), Some(0));
+        assert_eq!(nb.index(1, 1), Some(2));
+        assert_eq!(nb.index(2, 2), None);
+        assert_eq!(Neighborhood::Line.index(2, 0), Some(2));
+        assert_eq!(Neighborhood::Moore2.index(4, 4), Some(24));
+    }
+
+    #[test]
+    fn field_cells_in_pattern_order() {
+        let field = Field {
+            w: 3,
+            h: 3,
+            data: vec![b"abc".to_vec(), b"def".to_vec(), b"ghi".to_vec()],
+        };
+        let cells = |nb| field.neighborhood(1, 1, nb, Boundary::default());
+        assert_eq!(cells(Neighborhood::Moore), b"abcdefghi".to_vec());
+        assert_eq!(cells(Neighborhood::VonNeumann), b"bdefh".to_vec());
+        assert_eq!(cells(Neighborhood::Line), b"def".to_vec());
+        let moore2 = cells(Neighborhood::Moore2);
+        assert_eq!(moore2.len(), 25);
+        assert_eq!(moore2[12], b'e');
+        assert_eq!(&moore2[6..9], b"abc");
+    }
+}
@@ -0,0 +1,29 @@
+use crate::level::Level;
+
+// Several levels shipped as a single file, written as
+// `(name: "...", author: "...", version: "...", levels: [...])`. Everything
+// but the levels is optional.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Pack {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub author: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version: String,
+    pub levels: Vec<Level>,
+}
+
+impl Pack {
+    // Like `Basics 1.0 by coco`, leaving out what the pack does not say.
+    pub fn about(&self) -> String {
+        let mut about = self.name.clone();
+        if !self.version.is_empty() {
+            about = format!("{} {}", about, self.version);
+        }
+        if !self.author.is_empty() {
+            about = format!("{} by {}", about, self.author);
+        }
+        return about.trim().to_string();
+    }
+}
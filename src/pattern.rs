@@ -0,0 +1,130 @@
+// Pattern elements beyond literals, the `_` wildcard and the variables
+// `A`-`Z`: character classes declared per level, inequalities between the
+// variables of a rule and neighbor counts.
+use crate::grid;
+use serde::de::{Deserializer, Error};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+// A pattern symbol that matches any of `chars`, or anything but them when
+// negated. Written as `(symbol: '#', chars: "xy")` or
+// `(symbol: '!', chars: " ", negate: true)`.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharClass {
+    #[serde(
+        serialize_with = "grid::serialize_byte",
+        deserialize_with = "grid::deserialize_byte"
+    )]
+    pub symbol: u8,
+    #[serde(
+        serialize_with = "grid::serialize_text",
+        deserialize_with = "grid::deserialize_text"
+    )]
+    pub chars: Vec<u8>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub negate: bool,
+}
+
+impl CharClass {
+    pub fn matches(&self, n: u8) -> bool {
+        return self.chars.contains(&n) != self.negate;
+    }
+
+    // Legend entry like `# = any of "xy"`.
+    pub fn describe(&self) -> String {
+        let kind = if self.negate { "none of" } else { "any of" };
+        let chars = grid::to_string(&self.chars);
+        return format!("{} = {} \"{}\"", self.symbol as char, kind, chars);
+    }
+}
+
+// Turns a rule into a counting rule: besides matching its pattern, the
+// number of neighbors (all cells but the center) matching `of` has to be one
+// of `counts`. Written as `(of: 'x', counts: [2, 3])`.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Count {
+    #[serde(
+        serialize_with = "grid::serialize_byte",
+        deserialize_with = "grid::deserialize_byte"
+    )]
+    pub of: u8,
+    pub counts: Vec<usize>,
+}
+
+impl Count {
+    pub fn toggle(&mut self, n: usize) {
+        match self.counts.iter().position(|c| *c == n) {
+            Some(i) => {
+                self.counts.remove(i);
+            }
+            None => {
+                self.counts.push(n);
+                self.counts.sort();
+            }
+        }
+    }
+
+    // Like `'x' in 2, 3`.
+    pub fn describe(&self) -> String {
+        let counts = self
+            .counts
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        return format!("'{}' in {}", self.of as char, counts.join(", "));
+    }
+}
+
+// Lets level files write a count without wrapping it in `Some(...)`.
+pub fn serialize_count<S: Serializer>(count: &Option<Count>, s: S) -> Result<S::Ok, S::Error> {
+    return count.as_ref().unwrap().serialize(s);
+}
+
+pub fn deserialize_count<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Count>, D::Error> {
+    return Count::deserialize(d).map(Some);
+}
+
+// Two variables of a rule that must not be bound to the same byte. Written
+// as `"A != B"`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Distinct(pub u8, pub u8);
+
+impl Distinct {
+    // The same pair regardless of the order it was entered in.
+    pub fn new(a: u8, b: u8) -> Self {
+        return Distinct(a.min(b), a.max(b));
+    }
+}
+
+impl fmt::Display for Distinct {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{} != {}", self.0 as char, self.1 as char);
+    }
+}
+
+impl Serialize for Distinct {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        return s.serialize_str(&self.to_string());
+    }
+}
+
+impl<'de> Deserialize<'de> for Distinct {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(d)?;
+        let vars = text
+            .split("!=")
+            .map(|v| v.trim().as_bytes())
+            .collect::<Vec<_>>();
+        match vars[..] {
+            [[a], [b]] if a.is_ascii_uppercase() && b.is_ascii_uppercase() && a != b => {
+                return Ok(Distinct::new(*a, *b))
+            }
+            _ => {
+                return Err(D::Error::custom(format!(
+                    "\"{}\" is not of the form \"A != B\" with two different variables",
+                    text
+                )))
+            }
+        }
+    }
+}
@@ -1,47 +1,120 @@
 use crate::cursor_pos::CursorPos;
-use crate::field::Field;
 use crate::game_state::GameState;
-use crate::level::Level;
-use crate::rule::Rule;
+use crate::progress::Progress;
+use rulez::{
+    Automaton, Boundary, Conflict, CycleDetector, Field, Level, MatchPolicy, Neighborhood, Rule,
+    Stuck, Trace, UpdateMode,
+};
 use std::io::Stdout;
 use std::io::Write;
+use termion::color;
 use termion::event::{Event, Key};
 use termion::input::MouseTerminal;
 use termion::raw::RawTerminal;
 use termion::screen::AlternateScreen;
 
-const DOWN_RIGHT: &'static str = "╔";
-const DOWN_LEFT: &'static str = "╗";
-const UP_RIGHT: &'static str = "╚";
-const UP_LEFT: &'static str = "╝";
-const VERTICAL: &'static str = "║";
-//const VERTICAL_RIGHT: &'static str= "╠";
-//const VERTICAL_LEFT: &'static str= "╣";
-const HORIZONTAL: &'static str = "═";
-const UP_HORIZONTAL: &'static str = "╩";
-const DOWN_HORIZONTAL: &'static str = "╦";
-//const VERTICAL_HORIZONTAL: &'static str= "╬";
+const DOWN_RIGHT: &str = "╔";
+const DOWN_LEFT: &str = "╗";
+const UP_RIGHT: &str = "╚";
+const UP_LEFT: &str = "╝";
+const VERTICAL: &str = "║";
+//const VERTICAL_RIGHT: &str= "╠";
+//const VERTICAL_LEFT: &str= "╣";
+const HORIZONTAL: &str = "═";
+const UP_HORIZONTAL: &str = "╩";
+const DOWN_HORIZONTAL: &str = "╦";
+//const VERTICAL_HORIZONTAL: &str= "╬";
+const LOCKED_HORIZONTAL: &str = "─";
+
+// Background of the cells of rules the player may not change.
+const LOCKED_COLOR: u8 = 8;
+
+// Background colors that mark the cells changed by each rule.
+const RULE_COLORS: [u8; 6] = [1, 2, 3, 4, 5, 6];
+
+// How far End runs the automaton when it never reaches the goal.
+const MAX_RUN: usize = 1000;
+
+// Narrowest the level description is wrapped to, even if it then overlaps
+// the rules on small terminals.
+const MIN_TEXT_WIDTH: usize = 16;
 
 pub type Out = MouseTerminal<AlternateScreen<RawTerminal<Stdout>>>;
 
 pub struct PlayingUI {
+    // Where the progress of the level is saved, see `Game::levels`.
+    pub source: String,
     pub lvl: Level,
     pub cursor: CursorPos,
     pub animate: bool,
     pub cur_step: usize,
     pub field: Field,
+    pub history: Vec<Field>,
+    pub traces: Vec<Trace>,
+    pub conflicts: Vec<Vec<Conflict>>,
+    pub show_trace: bool,
+    pub skip_locked: bool,
+    pub cycles: CycleDetector,
+    pub stuck: Option<Stuck>,
+    pub jump: String,
+    pub pending_distinct: Option<u8>,
+    // Digits of a count being typed with alt.
+    pub pending_count: String,
+    pub hints_shown: usize,
 }
 
 impl PlayingUI {
-    pub fn new(lvl: Level) -> Self {
+    pub fn new(source: String, mut lvl: Level, progress: &Progress) -> Self {
+        Self::restore_rules(&source, &mut lvl, progress);
         let field = lvl.start.clone();
+        let cycles = CycleDetector::new(&field, lvl.auto.update.phases());
         return Self {
-            cursor: CursorPos::Pattern { i: 0, x: 0, y: 0 },
-            animate: false,
+            source,
+            cursor: CursorPos::start(&lvl),
+            animate: true,
             cur_step: 0,
             lvl,
+            history: vec![field.clone()],
+            traces: vec![],
+            conflicts: vec![],
+            show_trace: true,
+            skip_locked: true,
+            cycles,
+            stuck: None,
             field,
+            jump: String::new(),
+            pending_distinct: None,
+            pending_count: String::new(),
+            hints_shown: 0,
+        };
+    }
+
+    // Brings back the rules the player entered the last time this level was
+    // open. Saves that no longer fit the level's rule slots are ignored.
+    fn restore_rules(source: &str, lvl: &mut Level, progress: &Progress) {
+        let saved = match progress.get(source, lvl.id) {
+            Some(saved) if saved.rules.len() == lvl.auto.rules.len() => &saved.rules,
+            _ => return,
         };
+        for (rule, saved) in lvl.auto.rules.iter_mut().zip(saved.iter()) {
+            if !rule.mutable || saved.pattern.len() != rule.pattern.len() {
+                continue;
+            }
+            for (i, cell) in saved.pattern.iter().enumerate() {
+                if !rule.is_cell_locked(i) {
+                    rule.pattern[i] = *cell;
+                }
+            }
+            rule.replace = saved.replace;
+            rule.distinct = saved.distinct.clone();
+            // Whether a slot counts is up to the level, what it counts is not.
+            if rule.count.is_some() && saved.count.is_some() {
+                rule.count = saved.count.clone();
+            }
+            if lvl.auto.allow_symmetry {
+                rule.symmetry = saved.symmetry;
+            }
+        }
     }
 
     pub fn toggle(&mut self) {
@@ -55,9 +128,13 @@ impl PlayingUI {
     pub fn set_char(&mut self, chr: &char) {
         let mut buf = [0; 4];
         let chr = chr.encode_utf8(&mut buf).as_bytes()[0];
+        if self.cursor.is_locked(&self.lvl) {
+            return;
+        }
         match self.cursor {
-            CursorPos::Pattern { i, x, y } => {
-                self.lvl.auto.rules[i as usize].pattern[(x + y * 3) as usize] = chr
+            CursorPos::Pattern { i, .. } => {
+                let cell = self.cursor.cell(&self.lvl).unwrap();
+                self.lvl.auto.rules[i as usize].pattern[cell] = chr
             }
             CursorPos::Replace { i } => self.lvl.auto.rules[i as usize].replace = chr,
             _ => {}
@@ -65,51 +142,461 @@ impl PlayingUI {
         self.reset_field();
     }
 
+    pub fn cycle_symmetry(&mut self) {
+        let i = match self.cursor.rule() {
+            Some(i) if self.lvl.auto.allow_symmetry && self.lvl.auto.rules[i].mutable => i,
+            _ => return,
+        };
+        let rule = &mut self.lvl.auto.rules[i];
+        rule.symmetry = rule.symmetry.next();
+        self.reset_field();
+    }
+
+    pub fn show_hint(&mut self) {
+        self.hints_shown = (self.hints_shown + 1).min(self.lvl.hints.len());
+    }
+
     pub fn reset_field(&mut self) {
         self.field = self.lvl.start.clone();
+        self.history = vec![self.field.clone()];
+        self.traces.clear();
+        self.conflicts.clear();
+        self.cycles = CycleDetector::new(&self.field, self.lvl.auto.update.phases());
+        self.stuck = None;
         self.cur_step = 0;
     }
 
     pub fn update(&mut self) -> bool {
-        if self.field != self.lvl.goal {
-            self.field = self.lvl.auto.step(&self.field);
-            self.cur_step += 1;
-            return false;
-        } else {
+        if self.field == self.lvl.goal {
             return true;
         }
+        if self.animate {
+            self.step_forward();
+        }
+        return false;
+    }
+
+    // Fields of steps that were already computed are taken from the history,
+    // which stays valid until the rules change and `reset_field` clears it.
+    pub fn step_forward(&mut self) {
+        if self.cur_step + 1 == self.history.len() {
+            let (next, trace, conflicts) = self.lvl.auto.step_traced(&self.field, self.cur_step);
+            // Let the player look at the conflict before it scrolls away.
+            self.animate &= conflicts.is_empty();
+            if self.stuck.is_none() {
+                self.stuck = self.cycles.check(self.cur_step + 1, &next);
+                // Nothing new will happen, so stop instead of spinning forever.
+                self.animate &= self.stuck.is_none();
+            }
+            self.history.push(next);
+            self.traces.push(trace);
+            self.conflicts.push(conflicts);
+        }
+        self.cur_step += 1;
+        self.field = self.history[self.cur_step].clone();
+    }
+
+    pub fn step_back(&mut self) {
+        self.cur_step = self.cur_step.saturating_sub(1);
+        self.field = self.history[self.cur_step].clone();
+    }
+
+    // Moves to step `n`, stopping early if the goal comes first.
+    pub fn goto_step(&mut self, n: usize) {
+        while self.cur_step > n {
+            self.step_back();
+        }
+        while self.cur_step < n && self.field != self.lvl.goal {
+            self.step_forward();
+        }
+    }
+
+    pub fn jump_to_typed_step(&mut self) {
+        if let Ok(n) = self.jump.parse() {
+            self.animate = false;
+            self.goto_step(n);
+        }
+        self.jump.clear();
+    }
+
+    pub fn state_event(&mut self, key: Key) {
+        match key {
+            Key::Right => {
+                self.animate = false;
+                if self.field != self.lvl.goal {
+                    self.step_forward();
+                }
+            }
+            Key::Left => {
+                self.animate = false;
+                self.step_back();
+            }
+            Key::Home => {
+                self.animate = false;
+                self.goto_step(0);
+            }
+            Key::End => {
+                self.animate = false;
+                self.goto_step(MAX_RUN);
+            }
+            Key::Char('t') => self.show_trace = !self.show_trace,
+            Key::Char('l') => self.skip_locked = !self.skip_locked,
+            Key::Char(d) if d.is_ascii_digit() => self.jump.push(d),
+            Key::Char('\n') => self.jump_to_typed_step(),
+            _ => {}
+        }
+    }
+
+    // Everything the level says about itself, along the left edge. Only the
+    // first `hints` hints are shown.
+    pub fn draw_level_description(out: &mut Out, lvl: &Level, hints: usize) {
+        let (_w, h) = termion::terminal_size().unwrap();
+        let width = Self::text_width(lvl);
+        let mut lines = vec![lvl.name()];
+        if lvl.difficulty > 0 {
+            lines.push(format!("Difficulty: {}", lvl.stars()));
+        }
+        if !lvl.description.is_empty() {
+            lines.extend(wrap(&lvl.description, width));
+            lines.push(String::new());
+        }
+        // Empty edges are what players expect, anything else is worth a note.
+        if lvl.auto.boundary != Boundary::default() {
+            lines.push(format!("Edges: {}", lvl.auto.boundary.name()));
+        }
+        if lvl.auto.policy != MatchPolicy::default() {
+            lines.push(format!("Matching: {}", lvl.auto.policy.name()));
+        }
+        if lvl.auto.update != UpdateMode::default() {
+            lines.push(format!("Updates: {}", lvl.auto.update.name()));
+        }
+        if lvl.auto.allow_symmetry {
+            lines.push("ctrl+r: rule symmetry".to_string());
+        }
+        lines.extend(lvl.auto.classes.iter().map(|c| c.describe()));
+        for (i, hint) in lvl.hints.iter().take(hints).enumerate() {
+            lines.extend(wrap(&format!("Hint {}: {}", i + 1, hint), width));
+        }
+        if hints < lvl.hints.len() {
+            lines.push(format!("ctrl+g: hint ({} left)", lvl.hints.len() - hints));
+        }
+        // Text that does not fit above the bottom of the terminal is cut off.
+        let rows = (h as usize).saturating_sub(1).max(1);
+        if lines.len() > rows {
+            lines.truncate(rows - 1);
+            lines.push("…".to_string());
+        }
+        for (i, line) in lines.iter().enumerate() {
+            write!(out, "{}{}", termion::cursor::Goto(2, 2 + i as u16), line).unwrap();
+        }
+    }
+
+    // Text along the left edge wraps before it runs into the rule and state
+    // boxes.
+    pub fn text_width(lvl: &Level) -> usize {
+        let (w, _h) = termion::terminal_size().unwrap();
+        let state_x = (w as usize).saturating_sub(lvl.start.w * 3 + 8) / 2;
+        return (Self::rules_start(lvl).0 as usize)
+            .min(state_x)
+            .saturating_sub(3)
+            .max(MIN_TEXT_WIDTH);
+    }
+
+    // Starts or finishes entering a variable inequality: ctrl+d on a pattern
+    // cell holding a variable, then the variable it has to differ from.
+    pub fn distinct_event(
+        lvl: &mut Level,
+        cursor: &CursorPos,
+        pending: &mut Option<u8>,
+        key: Key,
+    ) -> bool {
+        let i = match cursor.rule() {
+            Some(i) => i,
+            None => return false,
+        };
+        match (key, pending.take()) {
+            (Key::Char(b), Some(a)) if Rule::is_var(b as u8) && b as u8 != a => {
+                lvl.auto.rules[i].toggle_distinct(a, b as u8);
+                return true;
+            }
+            (Key::Ctrl('d'), None) => {
+                let var = cursor.cell(lvl).map(|c| lvl.auto.rules[i].pattern[c]);
+                *pending = var.filter(|v| Rule::is_var(*v));
+            }
+            _ => {}
+        }
+        return false;
+    }
+
+    // Changes the counting part of the rule under the cursor: alt with digits
+    // toggles that count, alt with any other key counts that symbol. Counts
+    // are collected in `typed` and toggled as soon as another digit could not
+    // make a count that fits the neighborhood, or on alt+enter. Returns
+    // whether the rule changed.
+    pub fn count_event(lvl: &mut Level, cursor: &CursorPos, typed: &mut String, key: Key) -> bool {
+        let max = lvl.auto.neighborhood.cells() - 1;
+        let count = cursor.rule().and_then(|i| lvl.auto.rules[i].count.as_mut());
+        let count = match (key, count) {
+            (Key::Alt(_), Some(count)) => count,
+            _ => {
+                typed.clear();
+                return false;
+            }
+        };
+        match key {
+            Key::Alt(d) if d.is_ascii_digit() => {
+                typed.push(d);
+                let n = typed.parse::<usize>().unwrap_or(usize::MAX);
+                if n.saturating_mul(10) <= max {
+                    return false;
+                }
+                typed.clear();
+                if n <= max {
+                    count.toggle(n);
+                    return true;
+                }
+            }
+            Key::Alt('\r') | Key::Alt('\n') if !typed.is_empty() => {
+                count.toggle(typed.parse().unwrap());
+                typed.clear();
+                return true;
+            }
+            Key::Alt(c) if c == ' ' || c.is_ascii_graphic() => {
+                typed.clear();
+                count.of = c as u8;
+                return true;
+            }
+            _ => typed.clear(),
+        }
+        return false;
+    }
+
+    // Counting condition and inequalities of the rule under the cursor,
+    // below the rule boxes.
+    pub fn draw_rule_details(
+        out: &mut Out,
+        lvl: &Level,
+        cursor: &CursorPos,
+        pending: Option<u8>,
+        typed_count: &str,
+    ) {
+        let (x, y) = Self::rules_start(lvl);
+        let i = match cursor.rule() {
+            Some(i) => i,
+            None => return,
+        };
+        let rule = &lvl.auto.rules[i];
+        let mut text = vec![];
+        if let Some(count) = &rule.count {
+            text.push(format!("counts {}", count.describe()));
+        }
+        text.extend(rule.distinct.iter().map(|d| d.to_string()));
+        let mut hints = vec![];
+        match pending {
+            Some(a) => text.push(format!("{} != ?", a as char)),
+            None => {
+                let var = cursor.cell(lvl).map(|c| rule.pattern[c]);
+                if var.is_some_and(Rule::is_var) {
+                    hints.push("ctrl+d: make variables differ");
+                }
+            }
+        }
+        if !typed_count.is_empty() {
+            text.push(format!("count {}? (alt+enter)", typed_count));
+        }
+        if rule.count.is_some() {
+            hints.push("alt+digits: toggle count  alt+key: counted symbol");
+        }
+        if text.is_empty() && hints.is_empty() {
+            return;
+        }
+        let row = y + lvl.auto.neighborhood.height() as u16 + 4;
+        write!(out, "{}", termion::cursor::Goto(x, row)).unwrap();
+        write!(out, "rule {}: {}", i + 1, text.join(", ")).unwrap();
+        write!(out, "  {}", hints.join("  ")).unwrap();
+    }
+
+    // Reports where the rules disagreed in the last step.
+    pub fn draw_conflicts(&self, out: &mut Out, x: u16, y: u16) -> bool {
+        let conflicts = match self.cur_step.checked_sub(1) {
+            Some(i) if !self.conflicts[i].is_empty() => &self.conflicts[i],
+            _ => return false,
+        };
+        write!(out, "{}{}", termion::cursor::Goto(x, y), conflicts[0]).unwrap();
+        if conflicts.len() > 1 {
+            write!(out, " (+{} more cells)", conflicts.len() - 1).unwrap();
+        }
+        return true;
+    }
+
+    pub fn draw_stuck(&self, out: &mut Out, x: u16, y: u16) {
+        if self.draw_conflicts(out, x, y) {
+            return;
+        }
+        if let Some(stuck) = self.stuck {
+            write!(out, "{}stuck: {}", termion::cursor::Goto(x, y), stuck).unwrap();
+        }
+    }
+
+    pub fn draw_controls(&self, out: &mut Out, x: u16, y: u16) {
+        let state = if self.animate { "running" } else { "paused" };
+        write!(out, "{}", termion::cursor::Goto(x, y)).unwrap();
+        write!(out, "Step: {} ({})", self.cur_step, state).unwrap();
+        if !self.jump.is_empty() {
+            write!(out, "  go to step: {}", self.jump).unwrap();
+        }
+        if self.cursor.in_state() {
+            let help = [
+                "space: play/pause  left/right: step  home/end: first/last",
+                "N enter: go to step N  t: trace  l: skip locked rules",
+            ];
+            for (i, line) in help.iter().enumerate() {
+                write!(
+                    out,
+                    "{}{}",
+                    termion::cursor::Goto(x, y + 1 + i as u16),
+                    line
+                )
+                .unwrap();
+            }
+        }
     }
 
-    pub fn draw_level_description(out: &mut Out, level: usize) {
-        write!(out, "{}", termion::cursor::Goto(2, 2)).unwrap();
-        write!(out, "Level: {}", level).unwrap();
+    // Columns taken by one rule box including its left border.
+    pub fn rule_stride(nb: Neighborhood) -> usize {
+        return nb.width() + 1;
     }
 
-    pub fn draw_rules(out: &mut Out, rules: &[Rule], x: usize, y: usize) {
+    pub fn draw_rules(out: &mut Out, auto: &Automaton, x: usize, y: usize) {
+        let nb = auto.neighborhood;
+        let stride = Self::rule_stride(nb);
         write!(out, "{}", termion::cursor::Goto((x + 1) as u16, y as u16)).unwrap();
-        for (j, rule) in rules.iter().enumerate() {
-            for i in 0..3 {
+        for (j, rule) in auto.rules.iter().enumerate() {
+            for by in 0..nb.height() {
                 write!(
                     out,
                     "{}",
-                    termion::cursor::Goto((x + 1 + j * 4) as u16, (y + i + 1) as u16)
+                    termion::cursor::Goto((x + 1 + j * stride) as u16, (y + by + 1) as u16)
                 )
                 .unwrap();
-                out.write(&rule.pattern[i * 3..i * 3 + 3]).unwrap();
+                for bx in 0..nb.width() {
+                    match nb.index(bx, by) {
+                        Some(c) => {
+                            Self::write_rule_cell(out, rule.is_cell_locked(c), rule.pattern[c])
+                        }
+                        None => write!(out, " ").unwrap(),
+                    }
+                }
             }
         }
     }
 
-    pub fn draw_replacements(out: &mut Out, rules: &[Rule], x: usize, y: usize) {
+    fn write_rule_cell(out: &mut Out, locked: bool, cell: u8) {
+        if locked {
+            write!(out, "{}", color::Bg(color::AnsiValue(LOCKED_COLOR))).unwrap();
+        }
+        out.write_all(&[cell]).unwrap();
+        write!(out, "{}", color::Bg(color::Reset)).unwrap();
+    }
+
+    // Where the replacement of rule `j` is drawn relative to the rule box.
+    pub fn replacement_pos(nb: Neighborhood, j: usize, x: usize, y: usize) -> (u16, u16) {
+        let px = x + 1 + j * Self::rule_stride(nb) + nb.width() / 2;
+        return (px as u16, (y + nb.height() + 2) as u16);
+    }
+
+    pub fn draw_replacements(out: &mut Out, auto: &Automaton, x: usize, y: usize) {
         write!(out, "{}", termion::cursor::Goto((x + 1) as u16, y as u16)).unwrap();
-        for (j, rule) in rules.iter().enumerate() {
+        for (j, rule) in auto.rules.iter().enumerate() {
+            let (px, py) = Self::replacement_pos(auto.neighborhood, j, x, y);
+            write!(out, "{}", termion::cursor::Goto(px, py)).unwrap();
+            Self::write_rule_cell(out, !rule.mutable, rule.replace);
+        }
+    }
+
+    fn rule_color(i: usize) -> color::Bg<color::AnsiValue> {
+        return color::Bg(color::AnsiValue(RULE_COLORS[i % RULE_COLORS.len()]));
+    }
+
+    // The trace that produced the field of the current step, if it is shown.
+    pub fn current_trace(&self) -> Option<&Trace> {
+        if !self.show_trace || self.cur_step == 0 {
+            return None;
+        }
+        return self.traces.get(self.cur_step - 1);
+    }
+
+    // Paints every cell that changed in the last step with the color of the
+    // rule that changed it.
+    pub fn draw_trace(
+        out: &mut Out,
+        prev: &Field,
+        field: &Field,
+        trace: &Trace,
+        x: usize,
+        y: usize,
+    ) {
+        for (cx, cy) in field.coords() {
+            let rule = match &trace[cy][cx] {
+                Some(firing) if prev.data[cy][cx] != field.data[cy][cx] => firing.rule,
+                _ => continue,
+            };
             write!(
                 out,
-                "{}",
-                termion::cursor::Goto((x + 2 + j * 4) as u16, (y + 5) as u16)
+                "{}{}",
+                termion::cursor::Goto((x + 1 + cx) as u16, (y + 1 + cy) as u16),
+                Self::rule_color(rule)
+            )
+            .unwrap();
+            out.write_all(&[field.data[cy][cx]]).unwrap();
+            write!(out, "{}", color::Bg(color::Reset)).unwrap();
+        }
+    }
+
+    pub fn draw_trace_legend(out: &mut Out, auto: &Automaton, x: usize, y: usize) {
+        for (j, rule) in auto.rules.iter().enumerate() {
+            let (px, py) = Self::replacement_pos(auto.neighborhood, j, x, y);
+            write!(
+                out,
+                "{}{}",
+                termion::cursor::Goto(px, py),
+                Self::rule_color(j)
+            )
+            .unwrap();
+            out.write_all(&[rule.replace]).unwrap();
+            write!(out, "{}", color::Bg(color::Reset)).unwrap();
+        }
+    }
+
+    // Lists how often each rule fired and the bindings of its first match.
+    pub fn draw_trace_summary(out: &mut Out, rules: &[Rule], trace: &Trace, x: u16, y: u16) {
+        let mut line = 0;
+        for i in 0..rules.len() {
+            let firings = trace
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|f| f.rule == i)
+                .collect::<Vec<_>>();
+            if firings.is_empty() {
+                continue;
+            }
+            let mut bindings = firings[0].bindings.iter().collect::<Vec<_>>();
+            bindings.sort();
+            write!(
+                out,
+                "{}{} {}rule {}: {} cells",
+                termion::cursor::Goto(x, y + line),
+                Self::rule_color(i),
+                color::Bg(color::Reset),
+                i + 1,
+                firings.len()
             )
             .unwrap();
-            out.write(&[rule.replace]).unwrap();
+            for (var, val) in bindings {
+                write!(out, "  {}={}", *var as char, *val as char).unwrap();
+            }
+            line += 1;
         }
     }
 
@@ -123,7 +610,7 @@ impl PlayingUI {
                 termion::cursor::Goto((x + 1) as u16, (y + i + 1) as u16)
             )
             .unwrap();
-            out.write(line).unwrap();
+            out.write_all(line).unwrap();
         }
     }
 
@@ -159,10 +646,10 @@ impl PlayingUI {
         }
     }
 
-    pub fn draw_rule_box_top(out: &mut Out, n: usize) {
+    pub fn draw_rule_box_top(out: &mut Out, n: usize, w: usize) {
         write!(out, "{}", DOWN_RIGHT).unwrap();
         for i in 0..n {
-            write!(out, "{}{}{}", HORIZONTAL, HORIZONTAL, HORIZONTAL).unwrap();
+            write!(out, "{}", HORIZONTAL.repeat(w)).unwrap();
             if i != n - 1 {
                 write!(out, "{}", DOWN_HORIZONTAL).unwrap();
             }
@@ -170,17 +657,27 @@ impl PlayingUI {
         write!(out, "{}", DOWN_LEFT).unwrap();
     }
 
-    pub fn draw_rule_box_center(out: &mut Out, n: usize) {
+    pub fn draw_rule_box_center(out: &mut Out, n: usize, w: usize) {
         write!(out, "{}", VERTICAL).unwrap();
         for _i in 0..n {
-            write!(out, "   {}", VERTICAL).unwrap();
+            write!(out, "{}{}", " ".repeat(w), VERTICAL).unwrap();
         }
     }
 
-    pub fn draw_rule_box_bot(out: &mut Out, n: usize) {
+    // The arrow points at the replacement below the center column.
+    pub fn draw_rule_box_bot(out: &mut Out, n: usize, w: usize) {
+        let (left, right) = (
+            HORIZONTAL.repeat(w / 2 - 1),
+            HORIZONTAL.repeat(w - w / 2 - 2),
+        );
         write!(out, "{}", UP_RIGHT).unwrap();
         for i in 0..n {
-            write!(out, "{}▼{}", DOWN_HORIZONTAL, DOWN_HORIZONTAL).unwrap();
+            write!(
+                out,
+                "{}{}▼{}{}",
+                left, DOWN_HORIZONTAL, DOWN_HORIZONTAL, right
+            )
+            .unwrap();
             if i != n - 1 {
                 write!(out, "{}", UP_HORIZONTAL).unwrap();
             }
@@ -188,34 +685,76 @@ impl PlayingUI {
         write!(out, "{}", UP_LEFT).unwrap();
     }
 
-    pub fn draw_replacement_box_center(out: &mut Out, n: usize) {
+    pub fn draw_replacement_box_center(out: &mut Out, n: usize, w: usize) {
+        let (left, right) = (" ".repeat(w / 2), " ".repeat(w - w / 2 - 2));
         for _i in 0..n {
-            write!(out, " {} {}", VERTICAL, VERTICAL).unwrap();
+            write!(out, "{}{} {}{}", left, VERTICAL, VERTICAL, right).unwrap();
         }
     }
 
-    pub fn draw_replacement_box_bot(out: &mut Out, n: usize) {
+    pub fn draw_replacement_box_bot(out: &mut Out, n: usize, w: usize) {
+        let (left, right) = (" ".repeat(w / 2), " ".repeat(w - w / 2 - 2));
         for _i in 0..n {
-            write!(out, " {}{}{}", UP_RIGHT, HORIZONTAL, UP_LEFT).unwrap();
+            write!(
+                out,
+                "{}{}{}{}{}",
+                left, UP_RIGHT, HORIZONTAL, UP_LEFT, right
+            )
+            .unwrap();
         }
     }
 
-    pub fn draw_rule_box(out: &mut Out, x: u16, y: u16, n: usize) {
+    // Locked rules get a single instead of a double line on top.
+    pub fn draw_rule_box_locks(out: &mut Out, auto: &Automaton, x: u16, y: u16) {
+        let nb = auto.neighborhood;
+        let stride = Self::rule_stride(nb) as u16;
+        for (i, _) in auto.rules.iter().enumerate().filter(|(_, r)| !r.mutable) {
+            write!(
+                out,
+                "{}",
+                termion::cursor::Goto(x + 1 + stride * i as u16, y)
+            )
+            .unwrap();
+            write!(out, "{}", LOCKED_HORIZONTAL.repeat(nb.width())).unwrap();
+        }
+    }
+
+    pub fn draw_rule_box(out: &mut Out, x: u16, y: u16, auto: &Automaton) {
+        let n = auto.rules.len();
+        let (w, h) = (auto.neighborhood.width(), auto.neighborhood.height() as u16);
         write!(out, "{}", termion::cursor::Goto(x, y)).unwrap();
-        Self::draw_rule_box_top(out, n);
-        for i in 1..=3 {
+        Self::draw_rule_box_top(out, n, w);
+        for i in 1..=h {
             write!(out, "{}", termion::cursor::Goto(x, y + i)).unwrap();
-            Self::draw_rule_box_center(out, n);
+            Self::draw_rule_box_center(out, n, w);
+        }
+        write!(out, "{}", termion::cursor::Goto(x, y + h + 1)).unwrap();
+        Self::draw_rule_box_bot(out, n, w);
+        write!(out, "{}", termion::cursor::Goto(x, y + h + 2)).unwrap();
+        Self::draw_replacement_box_center(out, n, w);
+        write!(out, "{}", termion::cursor::Goto(x, y + h + 3)).unwrap();
+        Self::draw_replacement_box_bot(out, n, w);
+        Self::draw_rule_box_locks(out, auto, x, y);
+        Self::draw_rule_box_badges(out, auto, x, y);
+    }
+
+    // Counting rules are marked in the top left corner, rules with a symmetry
+    // carry its badge in the top right one.
+    pub fn draw_rule_box_badges(out: &mut Out, auto: &Automaton, x: u16, y: u16) {
+        let stride = Self::rule_stride(auto.neighborhood) as u16;
+        for (i, rule) in auto.rules.iter().enumerate() {
+            if rule.count.is_some() {
+                let bx = x + stride * i as u16 + 1;
+                write!(out, "{}Σ", termion::cursor::Goto(bx, y)).unwrap();
+            }
+            if let Some(badge) = rule.symmetry.badge() {
+                let bx = x + stride * (i as u16 + 1) - 1;
+                write!(out, "{}{}", termion::cursor::Goto(bx, y), badge).unwrap();
+            }
         }
-        write!(out, "{}", termion::cursor::Goto(x, y + 4)).unwrap();
-        Self::draw_rule_box_bot(out, n);
-        write!(out, "{}", termion::cursor::Goto(x, y + 5)).unwrap();
-        Self::draw_replacement_box_center(out, n);
-        write!(out, "{}", termion::cursor::Goto(x, y + 6)).unwrap();
-        Self::draw_replacement_box_bot(out, n);
     }
 
-    fn draw_state_box(out: &mut Out, x: u16, y: u16, w: usize, h: usize) {
+    pub fn draw_state_box(out: &mut Out, x: u16, y: u16, w: usize, h: usize) {
         write!(out, "{}", termion::cursor::Goto(x, y)).unwrap();
         Self::draw_state_top(out, w);
         for i in 0_u16..(h as u16) {
@@ -227,64 +766,120 @@ impl PlayingUI {
     }
 
     pub fn set_cursor(&self, out: &mut Out, lvl: &Level) {
-        let (x, y) = match self.cursor {
+        Self::draw_cursor(out, &self.cursor, lvl);
+    }
+
+    pub fn draw_cursor(out: &mut Out, cursor: &CursorPos, lvl: &Level) {
+        let (rule_x, rule_y) = Self::rules_start(lvl);
+        let stride = Self::rule_stride(lvl.auto.neighborhood) as isize;
+        let (x, y) = match *cursor {
             CursorPos::Pattern { i, x, y } => (
-                self.rules_start(lvl).0 as isize + i * 4 + x + 1,
-                self.rules_start(lvl).1 as isize + 1 + y,
+                rule_x as isize + i * stride + x + 1,
+                rule_y as isize + 1 + y,
             ),
-            CursorPos::Replace { i } => (self.rules_start(lvl).0 as isize + i * 4 + 2, 7),
+            CursorPos::Replace { i } => {
+                let (x, y) = Self::replacement_pos(
+                    lvl.auto.neighborhood,
+                    i as usize,
+                    rule_x as usize,
+                    rule_y as usize,
+                );
+                (x as isize, y as isize)
+            }
             CursorPos::State { .. } => (1, 1),
         };
         write!(out, "{}", termion::cursor::Goto(x as u16, y as u16)).unwrap();
     }
-    pub fn rules_start(&self, lvl: &Level) -> (u16, u16) {
+
+    pub fn rules_len(lvl: &Level) -> usize {
+        return lvl.auto.rules.len() * Self::rule_stride(lvl.auto.neighborhood) + 1;
+    }
+
+    pub fn rules_start(lvl: &Level) -> (u16, u16) {
         let (w, _h) = termion::terminal_size().unwrap();
-        let rule_boxes = lvl.auto.rules.len();
-        let rules_len: usize = rule_boxes * 4 + 1;
-        return (((w - rules_len as u16) / 2) as u16, 2);
+        return ((w - Self::rules_len(lvl) as u16) / 2, 2);
+    }
+
+    // Row of the state box, right below the rules and their replacements.
+    pub fn state_start(lvl: &Level) -> u16 {
+        return Self::rules_start(lvl).1 + lvl.auto.neighborhood.height() as u16 + 5;
     }
 
     pub fn draw(&self, out: &mut Out) {
         write!(out, "{}", termion::clear::All).unwrap();
         let (w, _h) = termion::terminal_size().unwrap();
-        let rule_boxes = self.lvl.auto.rules.len();
         let state_len = self.lvl.start.w * 3 + 8;
-        let rules_len: usize = rule_boxes * 4 + 1;
-        if w < rules_len as u16 {
+        if w < Self::rules_len(&self.lvl) as u16 {
             return Self::draw_terminal_to_small(out);
         }
         if w < state_len as u16 {
             return Self::draw_terminal_to_small(out);
         }
-        let (rule_x, rule_y) = self.rules_start(&self.lvl);
-        Self::draw_level_description(out, self.lvl.id);
-        Self::draw_rule_box(out, rule_x, rule_y, rule_boxes);
+        let (rule_x, rule_y) = Self::rules_start(&self.lvl);
+        let state_y = Self::state_start(&self.lvl);
+        Self::draw_level_description(out, &self.lvl, self.hints_shown);
+        Self::draw_rule_details(
+            out,
+            &self.lvl,
+            &self.cursor,
+            self.pending_distinct,
+            &self.pending_count,
+        );
+        Self::draw_rule_box(out, rule_x, rule_y, &self.lvl.auto);
         Self::draw_state_box(
             out,
-            ((w - state_len as u16) / 2) as u16,
-            9,
+            (w - state_len as u16) / 2,
+            state_y,
             self.lvl.start.w,
             self.lvl.start.h,
         );
-        Self::draw_rules(out, &self.lvl.auto.rules, rule_x as usize, rule_y as usize);
-        Self::draw_replacements(out, &self.lvl.auto.rules, rule_x as usize, rule_y as usize);
+        Self::draw_rules(out, &self.lvl.auto, rule_x as usize, rule_y as usize);
+        Self::draw_replacements(out, &self.lvl.auto, rule_x as usize, rule_y as usize);
         Self::draw_field(
             out,
             &self.lvl.start,
             ((w - state_len as u16) / 2) as usize,
-            9,
+            state_y as usize,
         );
         Self::draw_field(
             out,
             &self.field,
-            (((w - state_len as u16) / 2) as usize) + &self.lvl.start.w + 3,
-            9,
+            (((w - state_len as u16) / 2) as usize) + self.lvl.start.w + 3,
+            state_y as usize,
         );
         Self::draw_field(
             out,
             &self.lvl.goal,
-            (((w - state_len as u16) / 2) as usize) + 2 * &self.lvl.start.w + 6,
-            9,
+            (((w - state_len as u16) / 2) as usize) + 2 * self.lvl.start.w + 6,
+            state_y as usize,
+        );
+        if let Some(trace) = self.current_trace() {
+            Self::draw_trace(
+                out,
+                &self.history[self.cur_step - 1],
+                &self.field,
+                trace,
+                (((w - state_len as u16) / 2) as usize) + self.lvl.start.w + 3,
+                state_y as usize,
+            );
+            Self::draw_trace_legend(out, &self.lvl.auto, rule_x as usize, rule_y as usize);
+            Self::draw_trace_summary(
+                out,
+                &self.lvl.auto.rules,
+                trace,
+                (w - state_len as u16) / 2,
+                state_y + 6 + self.lvl.start.h as u16,
+            );
+        }
+        self.draw_stuck(
+            out,
+            (w - state_len as u16) / 2,
+            state_y + 2 + self.lvl.start.h as u16,
+        );
+        self.draw_controls(
+            out,
+            (w - state_len as u16) / 2,
+            state_y + 3 + self.lvl.start.h as u16,
         );
         self.set_cursor(out, &self.lvl);
         out.flush().unwrap();
@@ -305,17 +900,78 @@ impl PlayingUI {
     }
 
     pub fn event(&mut self, event: Event) -> Option<GameState> {
+        if let Event::Key(key) = event {
+            let mutable = self
+                .cursor
+                .rule()
+                .is_some_and(|i| self.lvl.auto.rules[i].mutable);
+            if mutable
+                && Self::distinct_event(
+                    &mut self.lvl,
+                    &self.cursor,
+                    &mut self.pending_distinct,
+                    key,
+                )
+            {
+                self.reset_field();
+                return None;
+            }
+            if self.pending_distinct.is_some() {
+                return None;
+            }
+            let (lvl, typed) = (&mut self.lvl, &mut self.pending_count);
+            if mutable && Self::count_event(lvl, &self.cursor, typed, key) {
+                self.reset_field();
+                return None;
+            }
+        }
         match event {
             Event::Key(Key::Char('q')) => return Some(GameState::Quit),
+            Event::Key(Key::Esc) => return Some(GameState::ToMenu),
+            Event::Key(Key::Char(' ')) => self.toggle(),
+            Event::Key(Key::Up) if self.skip_locked => {
+                self.cursor = self.cursor.skip_locked(&self.lvl, CursorPos::up)
+            }
+            Event::Key(Key::Down) if self.skip_locked => {
+                self.cursor = self.cursor.skip_locked(&self.lvl, CursorPos::down)
+            }
             Event::Key(Key::Up) => self.cursor = self.cursor.up(&self.lvl),
-            Event::Key(Key::Left) => self.cursor = self.cursor.left(&self.lvl),
-            Event::Key(Key::Right) => self.cursor = self.cursor.right(&self.lvl),
             Event::Key(Key::Down) => self.cursor = self.cursor.down(&self.lvl),
             Event::Key(Key::Backspace) => self.reset_field(),
-            Event::Key(Key::Char(' ')) => self.toggle(),
+            Event::Key(key) if self.cursor.in_state() => self.state_event(key),
+            Event::Key(Key::Left) if self.skip_locked => {
+                self.cursor = self.cursor.skip_locked(&self.lvl, CursorPos::left)
+            }
+            Event::Key(Key::Right) if self.skip_locked => {
+                self.cursor = self.cursor.skip_locked(&self.lvl, CursorPos::right)
+            }
+            Event::Key(Key::Left) => self.cursor = self.cursor.left(&self.lvl),
+            Event::Key(Key::Right) => self.cursor = self.cursor.right(&self.lvl),
+            Event::Key(Key::Ctrl('r')) => self.cycle_symmetry(),
+            Event::Key(Key::Ctrl('g')) => self.show_hint(),
             Event::Key(Key::Char(x)) => self.set_char(&x),
             _ => {}
         }
         return None;
     }
 }
+
+// Breaks `text` into lines of at most `width` characters at spaces. Line
+// breaks in the text are kept, words that are too long get a line of their own.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    return lines;
+}
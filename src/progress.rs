@@ -0,0 +1,135 @@
+use crate::campaign::BUILTIN;
+use rulez::Rule;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelProgress {
+    pub solved: bool,
+    pub best_steps: Option<usize>,
+    pub rules: Vec<Rule>,
+}
+
+// Everything the player achieved so far, keyed by where the levels come
+// from and then by level id, since every pack has its own level 1.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    pub sources: BTreeMap<String, BTreeMap<usize, LevelProgress>>,
+    // Save files from before there were packs only know the built-in
+    // campaign. Moved into `sources` when loading.
+    #[serde(skip_serializing)]
+    levels: BTreeMap<usize, LevelProgress>,
+    // Set when the save file exists but could not be read. Saving would
+    // replace it with what little was achieved in this session.
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+impl Progress {
+    pub fn path() -> Option<PathBuf> {
+        let data = env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+        return Some(data.join("rulez").join("progress.ron"));
+    }
+
+    // A missing save file means starting from scratch. A broken one is moved
+    // to `progress.ron.bak` so it is not overwritten, if that fails nothing
+    // is saved at all. Problems are reported on stderr, so this has to run
+    // before the game takes over the terminal.
+    pub fn load() -> Self {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+        let read_only = Self {
+            read_only: true,
+            ..Self::default()
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                eprintln!("{}: {}, progress will not be saved", path.display(), e);
+                return read_only;
+            }
+        };
+        let err = match ron::de::from_str::<Self>(&contents) {
+            Ok(mut progress) => {
+                let old = std::mem::take(&mut progress.levels);
+                if !old.is_empty() {
+                    progress.sources.entry(BUILTIN.to_string()).or_insert(old);
+                }
+                return progress;
+            }
+            Err(e) => e,
+        };
+        let backup = path.with_extension("ron.bak");
+        match fs::rename(&path, &backup) {
+            Ok(()) => {
+                eprintln!(
+                    "{}: {}, moved it to {} and started over",
+                    path.display(),
+                    err,
+                    backup.display()
+                );
+                return Self::default();
+            }
+            Err(e) => {
+                eprintln!(
+                    "{}: {}, could not move it aside ({}), progress will not be saved",
+                    path.display(),
+                    err,
+                    e
+                );
+                return read_only;
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if self.read_only {
+            return Err(io::Error::other("the save file could not be read"));
+        }
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default().compact_arrays(true),
+        )
+        .map_err(|e| io::Error::other(e.to_string()))?;
+        return fs::write(path, contents);
+    }
+
+    pub fn get(&self, source: &str, id: usize) -> Option<&LevelProgress> {
+        return self.sources.get(source).and_then(|levels| levels.get(&id));
+    }
+
+    pub fn is_solved(&self, source: &str, id: usize) -> bool {
+        return self.get(source, id).is_some_and(|p| p.solved);
+    }
+
+    fn entry(&mut self, source: &str, id: usize) -> &mut LevelProgress {
+        let levels = self.sources.entry(source.to_string()).or_default();
+        return levels.entry(id).or_default();
+    }
+
+    pub fn record_rules(&mut self, source: &str, id: usize, rules: &[Rule]) {
+        self.entry(source, id).rules = rules.to_vec();
+    }
+
+    pub fn record_solved(&mut self, source: &str, id: usize, steps: usize) {
+        let progress = self.entry(source, id);
+        progress.solved = true;
+        progress.best_steps = Some(progress.best_steps.map_or(steps, |best| best.min(steps)));
+    }
+}
@@ -1,20 +1,115 @@
+use crate::grid;
+use crate::neighborhood::Neighborhood;
+use crate::pattern::{self, CharClass, Count, Distinct};
+use crate::symmetry::Symmetry;
 use std::collections::HashMap;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Rule {
+    #[serde(
+        serialize_with = "grid::serialize_pattern",
+        deserialize_with = "grid::deserialize_pattern"
+    )]
     pub pattern: Vec<u8>,
+    #[serde(
+        serialize_with = "grid::serialize_byte",
+        deserialize_with = "grid::deserialize_byte"
+    )]
     pub replace: u8,
     pub mutable: bool,
+    // Pattern cells the player may not change, even if the rule is mutable.
+    // Empty when no cell is locked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locked: Vec<bool>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub symmetry: Symmetry,
+    // Pairs of variables that may not be bound to the same byte.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub distinct: Vec<Distinct>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "pattern::serialize_count",
+        deserialize_with = "pattern::deserialize_count"
+    )]
+    pub count: Option<Count>,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    return *value == T::default();
 }
 
 impl Rule {
-    fn is_var(p: u8) -> bool {
+    pub fn new(pattern: Vec<u8>, replace: u8) -> Self {
+        return Self {
+            pattern,
+            replace,
+            mutable: true,
+            locked: vec![],
+            symmetry: Symmetry::None,
+            distinct: vec![],
+            count: None,
+        };
+    }
+
+    pub fn is_cell_locked(&self, i: usize) -> bool {
+        return !self.mutable || self.locked.get(i) == Some(&true);
+    }
+
+    pub fn toggle_cell_lock(&mut self, i: usize) {
+        self.locked.resize(self.pattern.len(), false);
+        self.locked[i] = !self.locked[i];
+        if self.locked.iter().all(|l| !l) {
+            self.locked.clear();
+        }
+    }
+
+    pub fn toggle_distinct(&mut self, a: u8, b: u8) {
+        let pair = Distinct::new(a, b);
+        match self.distinct.iter().position(|d| *d == pair) {
+            Some(i) => {
+                self.distinct.remove(i);
+            }
+            None => self.distinct.push(pair),
+        }
+    }
+
+    pub fn is_var(p: u8) -> bool {
         return 0x40 < p && p <= 0x5a;
     }
-    fn matches(p: u8, n: u8, repl: &mut HashMap<u8, u8>) -> bool {
+    // A counting rule's neighbors are compared with the counted symbol like
+    // with a pattern cell, except that variables have to be bound already.
+    fn counts(
+        &self,
+        count: &Count,
+        cells: &[u8],
+        repl: &HashMap<u8, u8>,
+        classes: &[CharClass],
+    ) -> bool {
+        let center = cells.len() / 2;
+        let n = cells
+            .iter()
+            .enumerate()
+            .filter(|(i, c)| {
+                if *i == center {
+                    return false;
+                }
+                if Self::is_var(count.of) {
+                    return repl.get(&count.of) == Some(c);
+                }
+                return Self::matches(count.of, **c, &mut HashMap::new(), classes);
+            })
+            .count();
+        return count.counts.contains(&n);
+    }
+
+    fn matches(p: u8, n: u8, repl: &mut HashMap<u8, u8>, classes: &[CharClass]) -> bool {
         if p == 0x5f {
             return true;
         } // _ matches everything
+        if let Some(class) = classes.iter().find(|c| c.symbol == p) {
+            return class.matches(n);
+        }
         if Self::is_var(p) {
             if let Some(v) = repl.get(&p) {
                 return *v == n;
@@ -24,16 +119,189 @@ impl Rule {
         }
         return p == n;
     }
-    pub fn apply(&self, neighborhood: Vec<u8>) -> Option<u8> {
+    // The center cell sits in the middle of every neighborhood's pattern.
+    pub fn center(&self) -> usize {
+        return self.pattern.len() / 2;
+    }
+
+    // A rule that writes back the center it matched can never change a cell.
+    pub fn is_trivial(&self) -> bool {
+        return self.pattern.get(self.center()) == Some(&self.replace) && self.replace != 0x5f;
+    }
+
+    // The orientations `apply` tries, see `Symmetry::permutations`. They only
+    // depend on the rule and the neighborhood, so callers compute them once
+    // and not for every cell.
+    pub fn permutations(&self, nb: Neighborhood) -> Vec<Vec<usize>> {
+        return self.symmetry.permutations(nb);
+    }
+
+    // The cell's new value if the rule matches in any of the orientations in
+    // `perms`, along with the variable bindings of the match.
+    pub fn apply(
+        &self,
+        cells: &[u8],
+        perms: &[Vec<usize>],
+        classes: &[CharClass],
+    ) -> Option<(u8, HashMap<u8, u8>)> {
+        return perms
+            .iter()
+            .find_map(|perm| self.apply_mapped(cells, perm.iter().cloned(), classes));
+    }
+
+    // Matches pattern entry i against the cell at the i-th index of `map`.
+    // `Option::is_none_or` needs Rust 1.82, so this sticks to `map_or`.
+    #[allow(clippy::unnecessary_map_or)]
+    fn apply_mapped(
+        &self,
+        cells: &[u8],
+        map: impl Iterator<Item = usize>,
+        classes: &[CharClass],
+    ) -> Option<(u8, HashMap<u8, u8>)> {
         let mut repl = HashMap::new();
         if self
             .pattern
             .iter()
-            .zip(neighborhood.iter())
-            .all(|(p, n)| Self::matches(*p, *n, &mut repl))
+            .zip(map)
+            .all(|(p, i)| Self::matches(*p, cells[i], &mut repl, classes))
+            && self
+                .distinct
+                .iter()
+                .all(|Distinct(a, b)| !repl.contains_key(a) || repl.get(a) != repl.get(b))
+            && self
+                .count
+                .as_ref()
+                .map_or(true, |count| self.counts(count, cells, &repl, classes))
         {
-            return Some(*repl.get(&self.replace).unwrap_or(&self.replace));
+            let res = *repl.get(&self.replace).unwrap_or(&self.replace);
+            return Some((res, repl));
         }
         return None;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Matches in the only orientation of a rule without symmetry.
+    fn apply(rule: &Rule, cells: &[u8], classes: &[CharClass]) -> Option<u8> {
+        let perms = rule.permutations(Neighborhood::Moore);
+        return rule.apply(cells, &perms, classes).map(|(res, _)| res);
+    }
+
+    fn counting(pattern: &[u8], replace: u8, of: u8, counts: Vec<usize>) -> Rule {
+        let mut rule = Rule::new(pattern.to_vec(), replace);
+        rule.count = Some(Count { of, counts });
+        return rule;
+    }
+
+    #[test]
+    fn counts_neighbors() {
+        let birth = counting(b"____ ____", b'x', b'x', vec![3]);
+        assert_eq!(apply(&birth, b"xx_x ____", &[]), Some(b'x'));
+        assert_eq!(apply(&birth, b"xx__ ____", &[]), None);
+        assert_eq!(apply(&birth, b"xxxx ____", &[]), None);
+    }
+
+    #[test]
+    fn center_is_not_counted() {
+        let rule = counting(b"_________", b'y', b'x', vec![0]);
+        assert_eq!(apply(&rule, b"____x____", &[]), Some(b'y'));
+    }
+
+    #[test]
+    fn pattern_still_has_to_match() {
+        let rule = counting(b"____ ____", b'x', b'x', vec![1]);
+        assert_eq!(apply(&rule, b"x___o____", &[]), None);
+    }
+
+    #[test]
+    fn counts_bound_variable() {
+        let rule = counting(b"____A____", b'y', b'A', vec![2]);
+        assert_eq!(apply(&rule, b"o_o_o____", &[]), Some(b'y'));
+        assert_eq!(apply(&rule, b"o_o_x____", &[]), None);
+    }
+
+    #[test]
+    fn counts_class() {
+        let classes = [CharClass {
+            symbol: b'#',
+            chars: b"xy".to_vec(),
+            negate: false,
+        }];
+        let rule = counting(b"_________", b'z', b'#', vec![2]);
+        assert_eq!(apply(&rule, b"x__y_____", &classes), Some(b'z'));
+        assert_eq!(apply(&rule, b"x__o_____", &classes), None);
+    }
+
+    #[test]
+    fn several_counts() {
+        let survive = counting(b"____x____", b'x', b'x', vec![2, 3]);
+        assert_eq!(apply(&survive, b"xx__x____", &[]), Some(b'x'));
+        assert_eq!(apply(&survive, b"xx_xx____", &[]), Some(b'x'));
+        assert_eq!(apply(&survive, b"x___x____", &[]), None);
+    }
+
+    #[test]
+    fn negated_class() {
+        let classes = [CharClass {
+            symbol: b'!',
+            chars: b" ".to_vec(),
+            negate: true,
+        }];
+        let rule = Rule::new(b"_!__ ____".to_vec(), b'x');
+        assert_eq!(apply(&rule, b"_o__ ____", &classes), Some(b'x'));
+        assert_eq!(apply(&rule, b"_ __ ____", &classes), None);
+        let count = counting(b"_________", b'y', b'!', vec![1]);
+        assert_eq!(apply(&count, b"o   x    ", &classes), Some(b'y'));
+        assert_eq!(apply(&count, b"o o      ", &classes), None);
+    }
+
+    #[test]
+    fn distinct_variables() {
+        let mut rule = Rule::new(b"A___B___C".to_vec(), b'x');
+        rule.toggle_distinct(b'B', b'A');
+        assert_eq!(apply(&rule, b"o___p____", &[]), Some(b'x'));
+        assert_eq!(apply(&rule, b"o___o____", &[]), None);
+        // Only the listed pair has to differ.
+        assert_eq!(apply(&rule, b"o___p___o", &[]), Some(b'x'));
+        // Toggling the pair again removes it.
+        rule.toggle_distinct(b'A', b'B');
+        assert_eq!(apply(&rule, b"o___o____", &[]), Some(b'x'));
+    }
+
+    #[test]
+    fn distinct_unbound_variable() {
+        let mut rule = Rule::new(b"A___ ____".to_vec(), b'x');
+        rule.toggle_distinct(b'A', b'Z');
+        assert_eq!(apply(&rule, b"o___ ____", &[]), Some(b'x'));
+    }
+
+    #[test]
+    fn distinct_in_level_files() {
+        let read = |text: &str| ron::de::from_str::<Distinct>(text).map_err(|e| e.code.to_string());
+        assert!(read(r#""B != A""#) == Ok(Distinct(b'A', b'B')));
+        assert!(read(r#""A!=B""#) == Ok(Distinct(b'A', b'B')));
+        assert_eq!(Distinct::new(b'B', b'A').to_string(), "A != B");
+        let expected = "\"A != A\" is not of the form \"A != B\" with two different variables";
+        assert_eq!(read(r#""A != A""#).err().unwrap(), expected);
+        assert!(read(r#""A != x""#).is_err());
+        assert!(read(r#""A != B != C""#).is_err());
+        assert!(read(r#""A""#).is_err());
+    }
+
+    #[test]
+    fn toggle_keeps_counts_sorted() {
+        let mut count = Count {
+            of: b'x',
+            counts: vec![],
+        };
+        count.toggle(12);
+        count.toggle(3);
+        count.toggle(7);
+        count.toggle(3);
+        assert_eq!(count.counts, vec![7, 12]);
+        assert_eq!(count.describe(), "'x' in 7, 12");
+    }
+}
@@ -0,0 +1,357 @@
+use crate::automaton::Automaton;
+use crate::cycle::CycleDetector;
+use crate::level::Level;
+use crate::rule::Rule;
+use crate::symmetry::Symmetry;
+
+const WILDCARD: u8 = 0x5f;
+const FIRST_VAR: u8 = 0x41;
+
+// Bounded brute force search for a rule set that turns `start` into `goal`.
+// Candidates are ordered by the number of rules and then by the number of
+// non-wildcard pattern cells, so the first hit is a minimal solution. The
+// settings are fixed by `new`, which enumerates the candidate rules up front.
+pub struct Solver<'a> {
+    lvl: &'a Level,
+    max_steps: usize,
+    max_literals: usize,
+    alphabet: Vec<u8>,
+    candidates: Vec<Vec<Rule>>,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(lvl: &'a Level, max_steps: usize, max_literals: usize) -> Self {
+        let mut alphabet = lvl
+            .start
+            .data
+            .iter()
+            .chain(lvl.goal.data.iter())
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>();
+        alphabet.sort();
+        alphabet.dedup();
+        let mut solver = Self {
+            lvl,
+            max_steps,
+            max_literals,
+            alphabet,
+            candidates: vec![],
+        };
+        solver.candidates = (0..=max_literals)
+            .map(|n| solver.rules_with_literals(n))
+            .collect();
+        return solver;
+    }
+
+    pub fn solve(&self) -> Option<Automaton> {
+        let slots = self.lvl.auto.rules.iter().filter(|r| r.mutable).count();
+        if self.reaches_goal(&self.assemble(&[], false)) {
+            return Some(self.assemble(&[], true));
+        }
+        for n in 1..=slots {
+            for budget in 0..=n * self.max_literals {
+                let mut chosen = vec![];
+                if self.search(n, budget, &mut chosen) {
+                    return Some(self.assemble(&chosen, true));
+                }
+            }
+        }
+        return None;
+    }
+
+    fn search(&self, n: usize, budget: usize, chosen: &mut Vec<Rule>) -> bool {
+        if n == 0 {
+            return budget == 0 && self.reaches_goal(&self.assemble(chosen, false));
+        }
+        for literals in 0..=budget.min(self.max_literals) {
+            for rule in self.candidates[literals].iter() {
+                chosen.push(rule.clone());
+                if self.search(n - 1, budget - literals, chosen) {
+                    return true;
+                }
+                chosen.pop();
+            }
+        }
+        return false;
+    }
+
+    fn reaches_goal(&self, auto: &Automaton) -> bool {
+        let mut field = self.lvl.start.clone();
+        let mut cycles = CycleDetector::new(&field, auto.update.phases());
+        for step in 1..=self.max_steps + 1 {
+            if field == self.lvl.goal {
+                return true;
+            }
+            let (next, conflicts) = auto.step_checked(&field, step - 1);
+            if !conflicts.is_empty() {
+                return false;
+            }
+            field = next;
+            if cycles.check(step, &field).is_some() {
+                return false;
+            }
+        }
+        return false;
+    }
+
+    // Puts the chosen rules into the level's mutable slots, in order. The
+    // remaining slots are left out, or with `fill` get a rule that never
+    // fires, so the result has one rule per slot. Locked rules stay where the
+    // level designer put them and locked cells keep their value.
+    fn assemble(&self, chosen: &[Rule], fill: bool) -> Automaton {
+        let mut chosen = chosen.iter();
+        let rules = self
+            .lvl
+            .auto
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                if !rule.mutable {
+                    return Some(rule.clone());
+                }
+                let mut res = match chosen.next() {
+                    Some(res) => res.clone(),
+                    None if fill => return Some(self.filler(rule)),
+                    None => return None,
+                };
+                for (i, cell) in res.pattern.iter_mut().enumerate() {
+                    if rule.is_cell_locked(i) {
+                        *cell = rule.pattern[i];
+                    }
+                }
+                res.locked = rule.locked.clone();
+                res.count = rule.count.clone();
+                // Without permission the slot's symmetry is not the player's to choose.
+                if !self.lvl.auto.allow_symmetry {
+                    res.symmetry = rule.symmetry;
+                }
+                return Some(res);
+            })
+            .collect();
+        return Automaton {
+            rules,
+            ..self.lvl.auto.clone()
+        };
+    }
+
+    // A rule for an unused slot. Its center, or the first unlocked cell if
+    // the center is locked, asks for a byte no cell can hold, and it writes
+    // back its center, so it neither matches nor changes anything under any
+    // policy.
+    fn filler(&self, slot: &Rule) -> Rule {
+        let auto = &self.lvl.auto;
+        let never = (0x21..=0xff)
+            .find(|b| {
+                return *b != WILDCARD
+                    && !Rule::is_var(*b)
+                    && !self.alphabet.contains(b)
+                    && !auto.rules.iter().any(|r| r.replace == *b)
+                    && !auto.classes.iter().any(|c| c.symbol == *b)
+                    && auto.boundary.fill() != *b;
+            })
+            // A level uses far fewer bytes than there are.
+            .unwrap_or(0);
+        let mut res = slot.clone();
+        for (i, cell) in res.pattern.iter_mut().enumerate() {
+            if !slot.is_cell_locked(i) {
+                *cell = WILDCARD;
+            }
+        }
+        let center = res.center();
+        let unlocked = std::iter::once(center)
+            .chain(0..res.pattern.len())
+            .find(|i| !slot.is_cell_locked(*i));
+        if let Some(i) = unlocked {
+            res.pattern[i] = never;
+        }
+        res.replace = res.pattern[center];
+        return res;
+    }
+
+    // All rules with exactly `literals` non-wildcard pattern cells. Variables
+    // are introduced in cell order (A, then B, ...) to avoid enumerating
+    // renamings of the same rule.
+    fn rules_with_literals(&self, literals: usize) -> Vec<Rule> {
+        let mut patterns = vec![];
+        let mut cur = vec![WILDCARD; self.lvl.auto.neighborhood.cells()];
+        self.patterns(literals, 0, 0, &mut cur, &mut patterns);
+        let mut rules = vec![];
+        for (pattern, vars) in patterns {
+            let replacements = self
+                .alphabet
+                .iter()
+                .cloned()
+                .chain(FIRST_VAR..FIRST_VAR + vars);
+            for replace in replacements {
+                let rule = Rule::new(pattern.clone(), replace);
+                if rule.is_trivial() {
+                    continue;
+                }
+                for &symmetry in self.symmetries() {
+                    rules.push(Rule {
+                        symmetry,
+                        ..rule.clone()
+                    });
+                }
+            }
+        }
+        return rules;
+    }
+
+    fn symmetries(&self) -> &'static [Symmetry] {
+        if self.lvl.auto.allow_symmetry {
+            return &Symmetry::ALL;
+        }
+        return &[Symmetry::None];
+    }
+
+    fn patterns(
+        &self,
+        literals: usize,
+        pos: usize,
+        vars: u8,
+        cur: &mut Vec<u8>,
+        res: &mut Vec<(Vec<u8>, u8)>,
+    ) {
+        if literals == 0 {
+            res.push((cur.clone(), vars));
+            return;
+        }
+        if pos + literals > cur.len() {
+            return;
+        }
+        for p in pos..=(cur.len() - literals) {
+            for &chr in self.alphabet.iter() {
+                cur[p] = chr;
+                self.patterns(literals - 1, p + 1, vars, cur, res);
+            }
+            for var in FIRST_VAR..=FIRST_VAR + vars {
+                cur[p] = var;
+                let vars = vars.max(var - FIRST_VAR + 1);
+                self.patterns(literals - 1, p + 1, vars, cur, res);
+            }
+            cur[p] = WILDCARD;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::MatchPolicy;
+    use crate::field::Field;
+    use crate::validate::parse_level;
+
+    // A line grows down from the x, which takes one rule with one literal.
+    const FALLING: &str = r#"(
+        start: [" x ", "   ", "   "],
+        goal: [" x ", " x ", " x "],
+        auto: (
+            rules: [
+                (pattern: ["   ", "   ", "   "], replace: ' ', mutable: true),
+            ],
+        ),
+        id: 1,
+    )"#;
+
+    fn level(contents: &str) -> Level {
+        return parse_level("test", contents).ok().unwrap();
+    }
+
+    fn run(auto: &Automaton, start: &Field, steps: usize) -> Field {
+        let mut field = start.clone();
+        for step in 0..steps {
+            field = auto.step(&field, step);
+        }
+        return field;
+    }
+
+    fn literals(rule: &Rule) -> usize {
+        return rule.pattern.iter().filter(|c| **c != WILDCARD).count();
+    }
+
+    #[test]
+    fn finds_a_minimal_solution() {
+        let mut lvl = level(FALLING);
+        lvl.auto.rules.push(lvl.auto.rules[0].clone());
+        let auto = Solver::new(&lvl, 5, 2).solve().unwrap();
+        assert!(run(&auto, &lvl.start, 2) == lvl.goal);
+        assert_eq!(literals(&auto.rules[0]), 1);
+        assert_eq!(auto.rules[0].replace, b'x');
+        // The unused slot gets a rule that never fires.
+        assert!(auto.rules[1].is_trivial());
+        assert!(!lvl.start.data.concat().contains(&auto.rules[1].replace));
+    }
+
+    // Filling all blanks takes one rule. Under `Last` the rule for the unused
+    // second slot would win if it matched blanks, under `Unique` it would
+    // conflict with the first.
+    #[test]
+    fn unused_slots_do_not_interfere() {
+        for policy in [MatchPolicy::Last, MatchPolicy::Unique] {
+            let mut lvl = level(FALLING);
+            lvl.start = lvl.goal.clone();
+            lvl.start.data = vec![b"   ".to_vec(); 3];
+            lvl.goal.data = vec![b"xxx".to_vec(); 3];
+            lvl.auto.policy = policy;
+            lvl.auto.rules.push(lvl.auto.rules[0].clone());
+            let auto = Solver::new(&lvl, 5, 1).solve().unwrap();
+            assert_eq!(literals(&auto.rules[0]), 0);
+            assert_eq!(auto.rules[0].replace, b'x');
+            let (next, conflicts) = auto.step_checked(&lvl.start, 0);
+            assert!(next == lvl.goal);
+            assert!(conflicts.is_empty());
+        }
+    }
+
+    #[test]
+    fn filler_leaves_locked_cells() {
+        let mut lvl = level(FALLING);
+        lvl.auto.rules[0].pattern[4] = b'x';
+        lvl.auto.rules[0].toggle_cell_lock(4);
+        let filler = Solver::new(&lvl, 1, 0).filler(&lvl.auto.rules[0]);
+        assert_eq!(filler.pattern[4], b'x');
+        assert_eq!(filler.replace, b'x');
+        // Another cell asks for a byte that is never there instead.
+        assert!(filler.pattern[0] != WILDCARD && filler.pattern[0] != b' ');
+        assert!(filler.pattern[0] != b'x');
+    }
+
+    #[test]
+    fn solved_level_needs_no_rules() {
+        let mut lvl = level(FALLING);
+        lvl.goal = lvl.start.clone();
+        let auto = Solver::new(&lvl, 5, 2).solve().unwrap();
+        assert!(auto.rules.iter().all(|r| r.is_trivial()));
+    }
+
+    #[test]
+    fn gives_up_within_the_step_limit() {
+        let lvl = level(FALLING);
+        assert!(Solver::new(&lvl, 1, 1).solve().is_none());
+    }
+
+    #[test]
+    fn keeps_locked_rules_and_cells() {
+        let mut lvl = level(FALLING);
+        lvl.auto.rules.push(lvl.auto.rules[0].clone());
+        lvl.auto.rules[0].mutable = false;
+        lvl.auto.rules[1].pattern[4] = b' ';
+        lvl.auto.rules[1].toggle_cell_lock(4);
+        let auto = Solver::new(&lvl, 5, 2).solve().unwrap();
+        assert_eq!(auto.rules[0].pattern, lvl.auto.rules[0].pattern);
+        assert_eq!(auto.rules[1].pattern[4], b' ');
+        assert_eq!(auto.rules[1].locked, lvl.auto.rules[1].locked);
+        assert!(run(&auto, &lvl.start, 2) == lvl.goal);
+    }
+
+    #[test]
+    fn no_mutable_slots_no_solution() {
+        let mut lvl = level(FALLING);
+        for rule in lvl.auto.rules.iter_mut() {
+            rule.mutable = false;
+        }
+        assert!(Solver::new(&lvl, 5, 2).solve().is_none());
+    }
+}
@@ -0,0 +1,76 @@
+use crate::game_state::GameState;
+use crate::playing_ui::{Out, PlayingUI};
+use std::io::Write;
+use termion::event::{Event, Key};
+
+pub struct SuccessUI {
+    pub id: usize,
+    pub steps: usize,
+    pub rules_used: usize,
+    pub rule_slots: usize,
+    pub level: usize,
+    pub next: Option<usize>,
+    // Whether the progress has every level of the game solved.
+    pub all_solved: bool,
+}
+
+impl SuccessUI {
+    pub fn new(ui: &PlayingUI) -> Self {
+        let rules = &ui.lvl.auto.rules;
+        return Self {
+            id: ui.lvl.id,
+            steps: ui.cur_step,
+            rules_used: rules.iter().filter(|r| !r.is_trivial()).count(),
+            rule_slots: rules.len(),
+            level: 0,
+            next: None,
+            all_solved: false,
+        };
+    }
+
+    pub fn draw(&self, out: &mut Out) {
+        write!(out, "{}", termion::clear::All).unwrap();
+        let (w, h) = termion::terminal_size().unwrap();
+        let mut lines = vec![
+            format!("Level {} solved!", self.id),
+            String::new(),
+            format!("steps: {}", self.steps),
+            format!("rules used: {} of {}", self.rules_used, self.rule_slots),
+            String::new(),
+        ];
+        if self.all_solved {
+            lines.push("All levels complete!".to_string());
+            lines.push(String::new());
+        } else if self.next.is_none() {
+            lines.push("That was the last level.".to_string());
+            lines.push(String::new());
+        }
+        match self.next {
+            Some(_) => lines.push("n: next level  r: retry  m: menu".to_string()),
+            None => lines.push("r: retry  m: menu  q: quit".to_string()),
+        }
+        let width = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+        if (w as usize) < width || (h as usize) < lines.len() + 2 {
+            return PlayingUI::draw_terminal_to_small(out);
+        }
+        let x = (w - width as u16) / 2;
+        let y = (h - lines.len() as u16) / 2;
+        for (i, line) in lines.iter().enumerate() {
+            write!(out, "{}{}", termion::cursor::Goto(x, y + i as u16), line).unwrap();
+        }
+        out.flush().unwrap();
+    }
+
+    pub fn event(&mut self, event: Event) -> Option<GameState> {
+        match event {
+            Event::Key(Key::Char('q')) => return Some(GameState::Quit),
+            Event::Key(Key::Char('m')) | Event::Key(Key::Esc) => return Some(GameState::ToMenu),
+            Event::Key(Key::Char('r')) => return Some(GameState::Start(self.level)),
+            Event::Key(Key::Char('n')) | Event::Key(Key::Char('\n')) => {
+                return self.next.map(GameState::Start)
+            }
+            _ => {}
+        }
+        return None;
+    }
+}
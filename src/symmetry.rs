@@ -0,0 +1,161 @@
+use crate::neighborhood::Neighborhood;
+
+type Transform = fn(isize, isize) -> (isize, isize);
+
+// Orientations a rule's pattern is also tried in. Offsets are in screen
+// coordinates, so y grows downwards and a quarter turn is clockwise.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Symmetry {
+    #[default]
+    None,
+    // All four quarter turns.
+    Rot90,
+    // The pattern and its half turn.
+    Rot180,
+    // The pattern and its mirror image with left and right swapped.
+    MirrorH,
+    // The pattern and its mirror image with top and bottom swapped.
+    MirrorV,
+    // Every rotation and reflection of the square.
+    Full,
+}
+
+const ID: Transform = |x, y| (x, y);
+const ROT90: Transform = |x, y| (-y, x);
+const ROT180: Transform = |x, y| (-x, -y);
+const ROT270: Transform = |x, y| (y, -x);
+const FLIP_H: Transform = |x, y| (-x, y);
+const FLIP_V: Transform = |x, y| (x, -y);
+const DIAG: Transform = |x, y| (y, x);
+const ANTI_DIAG: Transform = |x, y| (-y, -x);
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 6] = [
+        Symmetry::None,
+        Symmetry::Rot90,
+        Symmetry::Rot180,
+        Symmetry::MirrorH,
+        Symmetry::MirrorV,
+        Symmetry::Full,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Symmetry::None => return "none",
+            Symmetry::Rot90 => return "rot90",
+            Symmetry::Rot180 => return "rot180",
+            Symmetry::MirrorH => return "mirror-h",
+            Symmetry::MirrorV => return "mirror-v",
+            Symmetry::Full => return "full",
+        }
+    }
+
+    // Single character shown on the rule box.
+    pub fn badge(&self) -> Option<char> {
+        match self {
+            Symmetry::None => return None,
+            Symmetry::Rot90 => return Some('↻'),
+            Symmetry::Rot180 => return Some('½'),
+            Symmetry::MirrorH => return Some('↔'),
+            Symmetry::MirrorV => return Some('↕'),
+            Symmetry::Full => return Some('*'),
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|s| s == self).unwrap();
+        return Self::ALL[(i + 1) % Self::ALL.len()];
+    }
+
+    fn transforms(&self) -> &'static [Transform] {
+        match self {
+            Symmetry::None => return &[ID],
+            Symmetry::Rot90 => return &[ID, ROT90, ROT180, ROT270],
+            Symmetry::Rot180 => return &[ID, ROT180],
+            Symmetry::MirrorH => return &[ID, FLIP_H],
+            Symmetry::MirrorV => return &[ID, FLIP_V],
+            Symmetry::Full => return &[ID, ROT90, ROT180, ROT270, FLIP_H, FLIP_V, DIAG, ANTI_DIAG],
+        }
+    }
+
+    // For every orientation, the cell each pattern entry is compared with.
+    // Orientations that leave the neighborhood, like a quarter turn of a
+    // line, are skipped.
+    pub fn permutations(&self, nb: Neighborhood) -> Vec<Vec<usize>> {
+        let offsets = nb.offsets().collect::<Vec<_>>();
+        return self
+            .transforms()
+            .iter()
+            .filter_map(|t| {
+                return offsets
+                    .iter()
+                    .map(|&(x, y)| offsets.iter().position(|o| *o == t(x, y)))
+                    .collect::<Option<Vec<_>>>();
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rule;
+
+    #[test]
+    fn orientations_per_symmetry() {
+        let counts = Symmetry::ALL
+            .iter()
+            .map(|s| s.permutations(Neighborhood::Moore).len());
+        assert_eq!(counts.collect::<Vec<_>>(), vec![1, 4, 2, 2, 2, 8]);
+    }
+
+    #[test]
+    fn every_orientation_is_a_permutation() {
+        for nb in Neighborhood::ALL.iter() {
+            for perm in Symmetry::Full.permutations(*nb) {
+                let mut sorted = perm.clone();
+                sorted.sort();
+                assert_eq!(sorted, (0..nb.cells()).collect::<Vec<_>>());
+                assert_eq!(perm[nb.cells() / 2], nb.cells() / 2);
+            }
+        }
+    }
+
+    #[test]
+    fn quarter_turn_is_clockwise() {
+        let perms = Symmetry::Rot90.permutations(Neighborhood::Moore);
+        assert_eq!(perms[0], (0..9).collect::<Vec<_>>());
+        // The cell above the center is compared with the one to its right.
+        assert_eq!(perms[1][1], 5);
+        assert_eq!(perms[1][0], 2);
+    }
+
+    #[test]
+    fn mirrors() {
+        let h = Symmetry::MirrorH.permutations(Neighborhood::Moore);
+        assert_eq!(h[1], vec![2, 1, 0, 5, 4, 3, 8, 7, 6]);
+        let v = Symmetry::MirrorV.permutations(Neighborhood::Moore);
+        assert_eq!(v[1], vec![6, 7, 8, 3, 4, 5, 0, 1, 2]);
+    }
+
+    #[test]
+    fn turns_that_leave_the_neighborhood_are_skipped() {
+        let perms = Symmetry::Rot90.permutations(Neighborhood::Line);
+        assert_eq!(perms, vec![vec![0, 1, 2], vec![2, 1, 0]]);
+    }
+
+    #[test]
+    fn rule_matches_in_every_orientation() {
+        let mut rule = Rule::new(b"_x__ ____".to_vec(), b'y');
+        let nb = Neighborhood::Moore;
+        let below = b"____ __x_".to_vec();
+        assert!(rule.apply(&below, &rule.permutations(nb), &[]).is_none());
+        rule.symmetry = Symmetry::Rot180;
+        let (res, _) = rule.apply(&below, &rule.permutations(nb), &[]).unwrap();
+        assert_eq!(res, b'y');
+        let right = b"____ x___".to_vec();
+        assert!(rule.apply(&right, &rule.permutations(nb), &[]).is_none());
+        rule.symmetry = Symmetry::Rot90;
+        assert!(rule.apply(&right, &rule.permutations(nb), &[]).is_some());
+    }
+}
@@ -0,0 +1,269 @@
+use crate::field::Field;
+
+// The order in which a step visits the cells. A step is a list of batches:
+// the cells of a batch all see the field as it was when the batch started,
+// later batches see what earlier ones wrote.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UpdateMode {
+    // Every cell sees the field of the previous step.
+    #[default]
+    Synchronous,
+    // Cells are updated in place, row by row from the top left.
+    RowMajor,
+    // Cells are updated in place in a shuffled order. The order only
+    // depends on the seed and the field, so runs can be reproduced.
+    Random {
+        seed: u64,
+    },
+    // First the cells where x + y is even, then the others.
+    Checkerboard,
+    // The field is split into 2x2 blocks that are all updated at once, and a
+    // cell only sees the cells of its own block. The rest of its neighborhood
+    // reads like cells outside the field. The blocks start at even
+    // coordinates on even steps and are shifted by one cell to the bottom
+    // right on odd steps, so things can move from block to block.
+    Margolus,
+}
+
+impl UpdateMode {
+    pub fn name(&self) -> String {
+        match self {
+            UpdateMode::Synchronous => return "synchronous".to_string(),
+            UpdateMode::RowMajor => return "row by row".to_string(),
+            UpdateMode::Random { seed } => return format!("random (seed {})", seed),
+            UpdateMode::Checkerboard => return "checkerboard".to_string(),
+            UpdateMode::Margolus => return "margolus".to_string(),
+        }
+    }
+
+    // The next mode when cycling through them in the editor.
+    pub fn next(&self, seed: u64) -> Self {
+        match self {
+            UpdateMode::Synchronous => return UpdateMode::RowMajor,
+            UpdateMode::RowMajor => return UpdateMode::Random { seed },
+            UpdateMode::Random { .. } => return UpdateMode::Checkerboard,
+            UpdateMode::Checkerboard => return UpdateMode::Margolus,
+            UpdateMode::Margolus => return UpdateMode::Synchronous,
+        }
+    }
+
+    // Number of steps after which the mode does the same again. A field that
+    // comes back after a different number of steps is not necessarily in a
+    // cycle.
+    pub fn phases(&self) -> usize {
+        match self {
+            UpdateMode::Margolus => return 2,
+            _ => return 1,
+        }
+    }
+
+    // Where the blocks of step `step` start, for modes that split the field
+    // into 2x2 blocks.
+    pub fn block_offset(&self, step: usize) -> Option<isize> {
+        match self {
+            UpdateMode::Margolus => return Some((step % 2) as isize),
+            _ => return None,
+        }
+    }
+
+    pub fn schedule(&self, field: &Field) -> Vec<Vec<(usize, usize)>> {
+        let rows = (0..field.h).flat_map(|y| (0..field.w).map(move |x| (x, y)));
+        match self {
+            UpdateMode::Synchronous | UpdateMode::Margolus => return vec![rows.collect()],
+            UpdateMode::RowMajor => return rows.map(|c| vec![c]).collect(),
+            UpdateMode::Random { seed } => {
+                let mut cells = rows.collect::<Vec<_>>();
+                let mut rng = SplitMix64(seed ^ fnv1a(field));
+                for i in (1..cells.len()).rev() {
+                    cells.swap(i, (rng.next() % (i as u64 + 1)) as usize);
+                }
+                return cells.into_iter().map(|c| vec![c]).collect();
+            }
+            UpdateMode::Checkerboard => {
+                let (even, odd) = rows.partition(|(x, y)| (x + y) % 2 == 0);
+                return vec![even, odd];
+            }
+        }
+    }
+}
+
+// Spelled out instead of using `std::hash` so the order of random updates
+// stays the same across compiler versions.
+fn fnv1a(field: &Field) -> u64 {
+    let mut hash = 0xcbf29ce484222325_u64;
+    for b in field.data.iter().flatten() {
+        hash = (hash ^ *b as u64).wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        return z ^ (z >> 31);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::{Automaton, MatchPolicy};
+    use crate::boundary::Boundary;
+    use crate::neighborhood::Neighborhood;
+    use crate::rule::Rule;
+
+    fn field(w: usize, h: usize) -> Field {
+        return Field {
+            w,
+            h,
+            data: vec![vec![b' '; w]; h],
+        };
+    }
+
+    // A cell becomes an x when the cell to its left is one, so how far the x
+    // spreads in one step shows the order the cells were updated in.
+    fn spread(update: UpdateMode) -> String {
+        return spread_at(update, 0);
+    }
+
+    fn spread_at(update: UpdateMode, step: usize) -> String {
+        let auto = Automaton {
+            neighborhood: Neighborhood::Line,
+            boundary: Boundary::default(),
+            policy: MatchPolicy::default(),
+            allow_symmetry: false,
+            classes: vec![],
+            update,
+            rules: vec![Rule::new(b"x _".to_vec(), b'x')],
+        };
+        let mut start = field(5, 1);
+        start.set(1, 0, b'x');
+        let next = auto.step(&start, step);
+        return String::from_utf8(next.data[0].clone()).unwrap();
+    }
+
+    // Every cell has to show up in a step, `times` times.
+    fn assert_covers(batches: &[Vec<(usize, usize)>], field: &Field, times: usize) {
+        let mut cells = batches.iter().flatten().cloned().collect::<Vec<_>>();
+        cells.sort();
+        let mut expected = field
+            .coords()
+            .flat_map(|c| vec![c; times])
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(cells, expected);
+    }
+
+    #[test]
+    fn synchronous() {
+        let f = field(3, 2);
+        let batches = UpdateMode::Synchronous.schedule(&f);
+        assert_eq!(batches.len(), 1);
+        assert_covers(&batches, &f, 1);
+        assert_eq!(spread(UpdateMode::Synchronous), " xx  ");
+    }
+
+    #[test]
+    fn row_major() {
+        let f = field(3, 2);
+        let batches = UpdateMode::RowMajor.schedule(&f);
+        assert_eq!(batches[..2], [vec![(0, 0)], vec![(1, 0)]]);
+        assert_eq!(batches[3], vec![(0, 1)]);
+        assert_covers(&batches, &f, 1);
+        assert_eq!(spread(UpdateMode::RowMajor), " xxxx");
+    }
+
+    #[test]
+    fn random() {
+        let f = field(4, 4);
+        let a = UpdateMode::Random { seed: 1 }.schedule(&f);
+        assert!(a.iter().all(|batch| batch.len() == 1));
+        assert_covers(&a, &f, 1);
+        assert!(a == UpdateMode::Random { seed: 1 }.schedule(&f));
+        assert!(a != UpdateMode::Random { seed: 2 }.schedule(&f));
+        let mut other = f.clone();
+        other.set(0, 0, b'x');
+        assert!(a != UpdateMode::Random { seed: 1 }.schedule(&other));
+    }
+
+    #[test]
+    fn checkerboard() {
+        let f = field(3, 2);
+        let batches = UpdateMode::Checkerboard.schedule(&f);
+        assert_eq!(batches[0], vec![(0, 0), (2, 0), (1, 1)]);
+        assert_eq!(batches[1], vec![(1, 0), (0, 1), (2, 1)]);
+        assert_eq!(spread(UpdateMode::Checkerboard), " xxx ");
+    }
+
+    #[test]
+    fn margolus() {
+        let f = field(4, 4);
+        let batches = UpdateMode::Margolus.schedule(&f);
+        assert_eq!(batches.len(), 1);
+        assert_covers(&batches, &f, 1);
+        assert_eq!(UpdateMode::Margolus.block_offset(0), Some(0));
+        assert_eq!(UpdateMode::Margolus.block_offset(3), Some(1));
+        assert_eq!(UpdateMode::Synchronous.block_offset(1), None);
+        // The x is in the block of cells 0 and 1 first, so it cannot reach
+        // cell 2. With the blocks shifted, cells 1 and 2 share one.
+        assert_eq!(spread_at(UpdateMode::Margolus, 0), " x   ");
+        assert_eq!(spread_at(UpdateMode::Margolus, 1), " xx  ");
+    }
+
+    #[test]
+    fn margolus_blocks_in_two_dimensions() {
+        // Every cell becomes the number of x it sees.
+        let auto = Automaton {
+            neighborhood: Neighborhood::Moore,
+            boundary: Boundary::default(),
+            policy: MatchPolicy::default(),
+            allow_symmetry: false,
+            classes: vec![],
+            update: UpdateMode::Margolus,
+            rules: vec![],
+        };
+        let mut f = field(3, 3);
+        f.set(1, 1, b'x');
+        let seen = |step: usize| {
+            return f
+                .coords()
+                .filter(|&(x, y)| auto.cells_around(&f, x, y, step).contains(&b'x'))
+                .collect::<Vec<_>>();
+        };
+        // (1, 1) is the corner of the top left block, then of the bottom
+        // right one.
+        assert_eq!(seen(0), vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+        assert_eq!(seen(1), vec![(1, 1), (1, 2), (2, 1), (2, 2)]);
+        assert_eq!(UpdateMode::Margolus.phases(), 2);
+        assert_eq!(UpdateMode::Checkerboard.phases(), 1);
+    }
+
+    #[test]
+    fn next_cycles_through_all() {
+        let mut mode = UpdateMode::Synchronous;
+        let mut names = vec![];
+        for _ in 0..5 {
+            mode = mode.next(7);
+            names.push(mode.name());
+        }
+        let expected = [
+            "row by row",
+            "random (seed 7)",
+            "checkerboard",
+            "margolus",
+            "synchronous",
+        ];
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn margolus_in_level_files() {
+        let mode = ron::de::from_str::<UpdateMode>("Margolus").unwrap();
+        assert!(mode == UpdateMode::Margolus);
+    }
+}
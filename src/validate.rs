@@ -0,0 +1,867 @@
+use crate::automaton::Automaton;
+use crate::field::Field;
+use crate::level::{Level, MAX_DIFFICULTY};
+use crate::pack::Pack;
+use crate::rule::Rule;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+
+pub enum LevelErrorKind {
+    Io(String),
+    Parse {
+        line: usize,
+        col: usize,
+        msg: String,
+    },
+    FieldHeight {
+        field: &'static str,
+        h: usize,
+        rows: usize,
+    },
+    FieldWidth {
+        field: &'static str,
+        row: usize,
+        w: usize,
+        cols: usize,
+    },
+    EmptyField {
+        field: &'static str,
+    },
+    SizeMismatch {
+        start: (usize, usize),
+        goal: (usize, usize),
+    },
+    NoRules,
+    PatternLength {
+        rule: usize,
+        len: usize,
+        expected: usize,
+    },
+    LockMaskLength {
+        rule: usize,
+        len: usize,
+        expected: usize,
+    },
+    CountOutOfRange {
+        rule: usize,
+        count: usize,
+        max: usize,
+    },
+    ReservedClassSymbol {
+        symbol: char,
+    },
+    DifficultyOutOfRange {
+        difficulty: u8,
+    },
+    EmptyHint {
+        hint: usize,
+    },
+    ClassSymbolInField {
+        symbol: char,
+        field: &'static str,
+    },
+    RuleCount {
+        slots: usize,
+        rules: usize,
+    },
+    LockedRuleChanged {
+        rule: usize,
+    },
+    SymmetryChanged {
+        rule: usize,
+    },
+    CountingChanged {
+        rule: usize,
+    },
+    DuplicateId {
+        id: usize,
+        first: String,
+    },
+    MissingIds {
+        from: usize,
+        to: usize,
+    },
+    ZeroId,
+    NoLevels,
+}
+
+pub struct LevelError {
+    pub path: String,
+    pub kind: LevelErrorKind,
+}
+
+// Rows, rules and hints are counted from 1 like everywhere players see them.
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use LevelErrorKind::*;
+        match &self.kind {
+            Io(msg) => write!(f, "{}: {}", self.path, msg),
+            Parse { line, col, msg } => write!(f, "{}:{}:{}: {}", self.path, line, col, msg),
+            FieldHeight { field, h, rows } => write!(
+                f,
+                "{}: {}: h is {} but data has {} rows",
+                self.path, field, h, rows
+            ),
+            FieldWidth {
+                field,
+                row,
+                w,
+                cols,
+            } => write!(
+                f,
+                "{}: {} row {}: w is {} but row has {} cells",
+                self.path,
+                field,
+                row + 1,
+                w,
+                cols
+            ),
+            EmptyField { field } => write!(f, "{}: {} has no cells", self.path, field),
+            SizeMismatch { start, goal } => write!(
+                f,
+                "{}: start is {}x{} but goal is {}x{}",
+                self.path, start.0, start.1, goal.0, goal.1
+            ),
+            NoRules => write!(f, "{}: auto: level has no rule slots", self.path),
+            PatternLength {
+                rule,
+                len,
+                expected,
+            } => write!(
+                f,
+                "{}: rule {}: pattern has {} entries, expected {}",
+                self.path,
+                rule + 1,
+                len,
+                expected
+            ),
+            LockMaskLength {
+                rule,
+                len,
+                expected,
+            } => write!(
+                f,
+                "{}: rule {}: locked has {} entries, expected 0 or {}",
+                self.path,
+                rule + 1,
+                len,
+                expected
+            ),
+            CountOutOfRange { rule, count, max } => write!(
+                f,
+                "{}: rule {}: cannot count {} of only {} neighbors",
+                self.path,
+                rule + 1,
+                count,
+                max
+            ),
+            DifficultyOutOfRange { difficulty } => write!(
+                f,
+                "{}: difficulty {} is not between 0 and {}",
+                self.path, difficulty, MAX_DIFFICULTY
+            ),
+            EmptyHint { hint } => write!(f, "{}: hint {} is empty", self.path, hint + 1),
+            ReservedClassSymbol { symbol } => write!(
+                f,
+                "{}: class '{}': the wildcard and variables cannot be classes",
+                self.path, symbol
+            ),
+            ClassSymbolInField { symbol, field } => write!(
+                f,
+                "{}: class '{}' also appears as a cell in {}",
+                self.path, symbol, field
+            ),
+            RuleCount { slots, rules } => write!(
+                f,
+                "{}: level has {} rule slots but {} rules were given",
+                self.path, slots, rules
+            ),
+            LockedRuleChanged { rule } => {
+                write!(
+                    f,
+                    "{}: rule {}: changes what the level locked",
+                    self.path,
+                    rule + 1
+                )
+            }
+            SymmetryChanged { rule } => write!(
+                f,
+                "{}: rule {}: the level does not allow changing its symmetry",
+                self.path,
+                rule + 1
+            ),
+            CountingChanged { rule } => write!(
+                f,
+                "{}: rule {}: counting rules and pattern rules cannot be swapped",
+                self.path,
+                rule + 1
+            ),
+            DuplicateId { id, first } => {
+                write!(f, "{}: id {} is already used by {}", self.path, id, first)
+            }
+            MissingIds { from, to } if from == to => {
+                write!(f, "{}: no level with id {}", self.path, from)
+            }
+            MissingIds { from, to } => {
+                write!(f, "{}: no levels with ids {} to {}", self.path, from, to)
+            }
+            ZeroId => write!(f, "{}: ids start at 1, not 0", self.path),
+            NoLevels => write!(f, "{}: no levels found", self.path),
+        }
+    }
+}
+
+impl LevelError {
+    pub fn new(path: &str, kind: LevelErrorKind) -> Self {
+        return Self {
+            path: path.to_string(),
+            kind,
+        };
+    }
+}
+
+fn load_ron<T: DeserializeOwned>(path: &str) -> Result<T, LevelError> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|e| LevelError::new(path, LevelErrorKind::Io(e.to_string())))?;
+    return parse_ron(path, &contents);
+}
+
+fn parse_ron<T: DeserializeOwned>(path: &str, contents: &str) -> Result<T, LevelError> {
+    return ron::de::from_str(contents).map_err(|e| {
+        let kind = LevelErrorKind::Parse {
+            line: e.span.start.line,
+            col: e.span.start.col,
+            msg: e.code.to_string(),
+        };
+        LevelError::new(path, kind)
+    });
+}
+
+pub fn load_level(path: &str) -> Result<Level, LevelError> {
+    return load_ron(path);
+}
+
+// Like `load_level` for levels that do not come from a file. `path` only
+// names the level in errors.
+pub fn parse_level(path: &str, contents: &str) -> Result<Level, LevelError> {
+    return parse_ron(path, contents);
+}
+
+// A rule set on its own, either as printed by `solve` or as a plain list.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RulesFile {
+    Automaton(Automaton),
+    Rules(Vec<Rule>),
+}
+
+pub fn load_rules(path: &str) -> Result<Vec<Rule>, LevelError> {
+    match load_ron(path)? {
+        RulesFile::Automaton(auto) => return Ok(auto.rules),
+        RulesFile::Rules(rules) => return Ok(rules),
+    }
+}
+
+// Puts the rules from `path` into the level's slots. Like a player, the rules
+// may only change what the level leaves open to them.
+pub fn substitute_rules(
+    path: &str,
+    lvl: &Level,
+    rules: Vec<Rule>,
+) -> Result<Level, Vec<LevelError>> {
+    use LevelErrorKind::*;
+    if rules.len() != lvl.auto.rules.len() {
+        let kind = RuleCount {
+            slots: lvl.auto.rules.len(),
+            rules: rules.len(),
+        };
+        return Err(vec![LevelError::new(path, kind)]);
+    }
+    let mut errors = vec![];
+    for (rule, (slot, new)) in lvl.auto.rules.iter().zip(rules.iter()).enumerate() {
+        let cells = slot.pattern.iter().zip(new.pattern.iter()).enumerate();
+        let locked_cells = cells
+            .filter(|(i, (a, b))| slot.is_cell_locked(*i) && a != b)
+            .count();
+        let rule_changed =
+            slot.replace != new.replace || slot.distinct != new.distinct || slot.count != new.count;
+        if locked_cells > 0 || (!slot.mutable && rule_changed) {
+            errors.push(LevelError::new(path, LockedRuleChanged { rule }));
+        }
+        let symmetry_fixed = !slot.mutable || !lvl.auto.allow_symmetry;
+        if symmetry_fixed && slot.symmetry != new.symmetry {
+            errors.push(LevelError::new(path, SymmetryChanged { rule }));
+        }
+        if slot.count.is_some() != new.count.is_some() {
+            errors.push(LevelError::new(path, CountingChanged { rule }));
+        }
+    }
+    let mut res = lvl.clone();
+    for (slot, new) in res.auto.rules.iter_mut().zip(rules) {
+        *slot = Rule {
+            mutable: slot.mutable,
+            locked: slot.locked.clone(),
+            ..new
+        };
+    }
+    errors.extend(validate_level(path, &res));
+    if errors.is_empty() {
+        return Ok(res);
+    }
+    return Err(errors);
+}
+
+fn validate_field(path: &str, name: &'static str, field: &Field, errors: &mut Vec<LevelError>) {
+    use LevelErrorKind::*;
+    if field.w == 0 || field.h == 0 {
+        errors.push(LevelError::new(path, EmptyField { field: name }));
+    }
+    if field.data.len() != field.h {
+        let kind = FieldHeight {
+            field: name,
+            h: field.h,
+            rows: field.data.len(),
+        };
+        errors.push(LevelError::new(path, kind));
+    }
+    for (row, line) in field.data.iter().enumerate() {
+        if line.len() != field.w {
+            let kind = FieldWidth {
+                field: name,
+                row,
+                w: field.w,
+                cols: line.len(),
+            };
+            errors.push(LevelError::new(path, kind));
+        }
+    }
+}
+
+pub fn validate_level(path: &str, lvl: &Level) -> Vec<LevelError> {
+    use LevelErrorKind::*;
+    let mut errors = vec![];
+    validate_field(path, "start", &lvl.start, &mut errors);
+    validate_field(path, "goal", &lvl.goal, &mut errors);
+    if (lvl.start.w, lvl.start.h) != (lvl.goal.w, lvl.goal.h) {
+        let kind = SizeMismatch {
+            start: (lvl.start.w, lvl.start.h),
+            goal: (lvl.goal.w, lvl.goal.h),
+        };
+        errors.push(LevelError::new(path, kind));
+    }
+    if lvl.auto.rules.is_empty() {
+        errors.push(LevelError::new(path, NoRules));
+    }
+    if lvl.difficulty > MAX_DIFFICULTY {
+        let kind = DifficultyOutOfRange {
+            difficulty: lvl.difficulty,
+        };
+        errors.push(LevelError::new(path, kind));
+    }
+    for (hint, text) in lvl.hints.iter().enumerate() {
+        if text.trim().is_empty() {
+            errors.push(LevelError::new(path, EmptyHint { hint }));
+        }
+    }
+    // A class symbol could not be told apart from the same byte in a field.
+    for class in lvl.auto.classes.iter() {
+        let symbol = class.symbol as char;
+        if class.symbol == 0x5f || Rule::is_var(class.symbol) {
+            errors.push(LevelError::new(path, ReservedClassSymbol { symbol }));
+        }
+        for (field, f) in [("start", &lvl.start), ("goal", &lvl.goal)] {
+            if f.data.iter().flatten().any(|b| *b == class.symbol) {
+                let kind = ClassSymbolInField { symbol, field };
+                errors.push(LevelError::new(path, kind));
+            }
+        }
+    }
+    let expected = lvl.auto.neighborhood.cells();
+    for (rule, r) in lvl.auto.rules.iter().enumerate() {
+        if r.pattern.len() != expected {
+            let len = r.pattern.len();
+            let kind = PatternLength {
+                rule,
+                len,
+                expected,
+            };
+            errors.push(LevelError::new(path, kind));
+        }
+        let max = expected - 1;
+        let counts = r.count.iter().flat_map(|c| c.counts.iter());
+        for &count in counts.filter(|c| **c > max) {
+            errors.push(LevelError::new(path, CountOutOfRange { rule, count, max }));
+        }
+        if !r.locked.is_empty() && r.locked.len() != expected {
+            let len = r.locked.len();
+            let kind = LockMaskLength {
+                rule,
+                len,
+                expected,
+            };
+            errors.push(LevelError::new(path, kind));
+        }
+    }
+    return errors;
+}
+
+// Checks that the ids of a whole campaign are unique and run from 1 without
+// gaps. `source` is used as the location of errors that concern no single
+// file.
+pub fn validate_ids(source: &str, levels: &[(String, Level)]) -> Vec<LevelError> {
+    use LevelErrorKind::*;
+    let mut errors = vec![];
+    if levels.is_empty() {
+        errors.push(LevelError::new(source, NoLevels));
+    }
+    let mut seen: HashMap<usize, &str> = HashMap::new();
+    for (path, lvl) in levels.iter() {
+        if lvl.id == 0 {
+            errors.push(LevelError::new(path, ZeroId));
+            continue;
+        }
+        match seen.get(&lvl.id) {
+            Some(first) => {
+                let kind = DuplicateId {
+                    id: lvl.id,
+                    first: first.to_string(),
+                };
+                errors.push(LevelError::new(path, kind));
+            }
+            None => {
+                seen.insert(lvl.id, path);
+            }
+        }
+    }
+    // Ids can be anything up to usize::MAX, so gaps are reported as ranges.
+    let mut ids = seen.keys().cloned().collect::<Vec<_>>();
+    ids.sort();
+    let mut next = 1;
+    for id in ids {
+        if id > next {
+            errors.push(LevelError::new(
+                source,
+                MissingIds {
+                    from: next,
+                    to: id - 1,
+                },
+            ));
+        }
+        next = id.saturating_add(1);
+    }
+    return errors;
+}
+
+// Loads and validates `paths`, returning the levels ordered by id. When they
+// form a whole campaign living in `dir`, their ids are checked as well.
+pub fn load_levels(
+    dir: Option<&str>,
+    paths: &[String],
+) -> Result<Vec<(String, Level)>, Vec<LevelError>> {
+    let mut levels = vec![];
+    let mut errors = vec![];
+    for path in paths.iter() {
+        match load_level(path) {
+            Ok(lvl) => {
+                errors.extend(validate_level(path, &lvl));
+                levels.push((path.clone(), lvl));
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    levels.sort_by_key(|(_, lvl)| lvl.id);
+    if let Some(dir) = dir {
+        errors.extend(validate_ids(dir, &levels));
+    }
+    if errors.is_empty() {
+        return Ok(levels);
+    }
+    return Err(errors);
+}
+
+// The levels of a pack with the name each one is reported under.
+pub type PackLevels = Vec<(String, Level)>;
+
+// Loads and validates the levels of a pack. They are moved out of the
+// returned pack and ordered by id. Errors in a level are reported at
+// `<pack>[<index>]`, counting from 0 like the list in the file.
+pub fn load_pack(path: &str) -> Result<(Pack, PackLevels), Vec<LevelError>> {
+    let mut pack: Pack = load_ron(path).map_err(|e| vec![e])?;
+    let mut levels = std::mem::take(&mut pack.levels)
+        .into_iter()
+        .enumerate()
+        .map(|(i, lvl)| (format!("{}[{}]", path, i), lvl))
+        .collect::<Vec<_>>();
+    let mut errors = levels
+        .iter()
+        .flat_map(|(name, lvl)| validate_level(name, lvl))
+        .collect::<Vec<_>>();
+    levels.sort_by_key(|(_, lvl)| lvl.id);
+    errors.extend(validate_ids(path, &levels));
+    if errors.is_empty() {
+        return Ok((pack, levels));
+    }
+    return Err(errors);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::{CharClass, Count};
+    use crate::symmetry::Symmetry;
+    use LevelErrorKind::*;
+
+    const LEVEL: &str = r#"(
+        hints: ["Look up."],
+        start: [
+            " x ",
+            "   ",
+            "   ",
+        ],
+        goal: [
+            " x ",
+            " x ",
+            " x ",
+        ],
+        auto: (
+            rules: [
+                (
+                    pattern: [
+                        " x ",
+                        "   ",
+                        "   ",
+                    ],
+                    replace: 'x',
+                    mutable: true,
+                ),
+            ],
+        ),
+        id: 1,
+    )"#;
+
+    fn level() -> Level {
+        return parse_level("test", LEVEL).ok().unwrap();
+    }
+
+    fn kinds(errors: Vec<LevelError>) -> Vec<LevelErrorKind> {
+        return errors.into_iter().map(|e| e.kind).collect();
+    }
+
+    fn campaign(ids: &[usize]) -> Vec<(String, Level)> {
+        return ids
+            .iter()
+            .map(|&id| (format!("level{}.ron", id), Level { id, ..level() }))
+            .collect();
+    }
+
+    #[test]
+    fn valid_level() {
+        assert!(validate_level("test", &level()).is_empty());
+        assert!(validate_ids("test", &campaign(&[1, 2, 3])).is_empty());
+    }
+
+    #[test]
+    fn io() {
+        let e = load_level("does/not/exist.ron").err().unwrap();
+        assert!(matches!(e.kind, Io(_)));
+        assert!(e.to_string().starts_with("does/not/exist.ron: "));
+    }
+
+    #[test]
+    fn parse() {
+        let e = parse_level("test", "(\n  id: x,\n)").err().unwrap();
+        assert!(matches!(e.kind, Parse { line: 2, .. }));
+    }
+
+    #[test]
+    fn ragged_field_is_a_parse_error() {
+        let contents = LEVEL.replacen("\"   \",", "\"  \",", 1);
+        let e = parse_level("test", &contents).err().unwrap();
+        assert!(matches!(e.kind, Parse { .. }));
+        assert!(e.to_string().contains("row 2 has 2 cells but row 1 has 3"));
+    }
+
+    #[test]
+    fn field_height() {
+        let mut lvl = level();
+        lvl.start.data.pop();
+        let errors = kinds(validate_level("test", &lvl));
+        assert!(matches!(
+            errors[..],
+            [FieldHeight {
+                field: "start",
+                h: 3,
+                rows: 2
+            }]
+        ));
+    }
+
+    #[test]
+    fn field_width() {
+        let mut lvl = level();
+        lvl.goal.data[1].pop();
+        let errors = validate_level("test", &lvl);
+        assert_eq!(
+            errors[0].to_string(),
+            "test: goal row 2: w is 3 but row has 2 cells"
+        );
+        assert!(matches!(
+            kinds(errors)[..],
+            [FieldWidth {
+                field: "goal",
+                row: 1,
+                w: 3,
+                cols: 2
+            }]
+        ));
+    }
+
+    #[test]
+    fn empty_field() {
+        let mut lvl = level();
+        lvl.start.resize(0, 0);
+        lvl.goal.resize(0, 0);
+        let errors = kinds(validate_level("test", &lvl));
+        assert!(matches!(
+            errors[..],
+            [EmptyField { field: "start" }, EmptyField { field: "goal" }]
+        ));
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let mut lvl = level();
+        lvl.goal.resize(4, 3);
+        let errors = kinds(validate_level("test", &lvl));
+        assert!(matches!(
+            errors[..],
+            [SizeMismatch {
+                start: (3, 3),
+                goal: (4, 3)
+            }]
+        ));
+    }
+
+    #[test]
+    fn no_rules() {
+        let mut lvl = level();
+        lvl.auto.rules.clear();
+        assert!(matches!(kinds(validate_level("test", &lvl))[..], [NoRules]));
+    }
+
+    #[test]
+    fn pattern_length() {
+        let mut lvl = level();
+        lvl.auto.rules[0].pattern.pop();
+        let e = validate_level("test", &lvl).remove(0);
+        assert!(matches!(
+            e.kind,
+            PatternLength {
+                rule: 0,
+                len: 8,
+                expected: 9
+            }
+        ));
+        assert_eq!(
+            e.to_string(),
+            "test: rule 1: pattern has 8 entries, expected 9"
+        );
+    }
+
+    #[test]
+    fn lock_mask_length() {
+        let mut lvl = level();
+        lvl.auto.rules[0].locked = vec![true; 3];
+        let errors = kinds(validate_level("test", &lvl));
+        assert!(matches!(
+            errors[..],
+            [LockMaskLength {
+                rule: 0,
+                len: 3,
+                expected: 9
+            }]
+        ));
+    }
+
+    #[test]
+    fn count_out_of_range() {
+        let mut lvl = level();
+        lvl.auto.rules[0].count = Some(Count {
+            of: b'x',
+            counts: vec![8, 9],
+        });
+        let errors = kinds(validate_level("test", &lvl));
+        assert!(matches!(
+            errors[..],
+            [CountOutOfRange {
+                rule: 0,
+                count: 9,
+                max: 8
+            }]
+        ));
+    }
+
+    #[test]
+    fn reserved_class_symbol() {
+        let mut lvl = level();
+        for symbol in [b'_', b'A'] {
+            lvl.auto.classes = vec![CharClass {
+                symbol,
+                chars: b"x".to_vec(),
+                negate: false,
+            }];
+            let errors = kinds(validate_level("test", &lvl));
+            assert!(
+                matches!(errors[..], [ReservedClassSymbol { symbol: s }] if s == symbol as char)
+            );
+        }
+    }
+
+    #[test]
+    fn class_symbol_in_field() {
+        let mut lvl = level();
+        lvl.auto.classes = vec![CharClass {
+            symbol: b'x',
+            chars: b"y".to_vec(),
+            negate: false,
+        }];
+        let errors = kinds(validate_level("test", &lvl));
+        assert!(matches!(
+            errors[..],
+            [
+                ClassSymbolInField {
+                    symbol: 'x',
+                    field: "start"
+                },
+                ClassSymbolInField {
+                    symbol: 'x',
+                    field: "goal"
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn difficulty_out_of_range() {
+        let mut lvl = level();
+        lvl.difficulty = MAX_DIFFICULTY;
+        assert!(validate_level("test", &lvl).is_empty());
+        lvl.difficulty = MAX_DIFFICULTY + 1;
+        let errors = kinds(validate_level("test", &lvl));
+        assert!(matches!(
+            errors[..],
+            [DifficultyOutOfRange { difficulty: 6 }]
+        ));
+    }
+
+    #[test]
+    fn empty_hint() {
+        let mut lvl = level();
+        lvl.hints.push(" ".to_string());
+        let e = validate_level("test", &lvl).remove(0);
+        assert!(matches!(e.kind, EmptyHint { hint: 1 }));
+        assert_eq!(e.to_string(), "test: hint 2 is empty");
+    }
+
+    #[test]
+    fn rule_count() {
+        let lvl = level();
+        let errors = kinds(substitute_rules("rules", &lvl, vec![]).err().unwrap());
+        assert!(matches!(errors[..], [RuleCount { slots: 1, rules: 0 }]));
+    }
+
+    #[test]
+    fn substituted_rules_fill_the_slots() {
+        let lvl = level();
+        let mut rule = lvl.auto.rules[0].clone();
+        rule.pattern[1] = b'_';
+        let res = substitute_rules("rules", &lvl, vec![rule]).ok().unwrap();
+        assert_eq!(res.auto.rules[0].pattern[1], b'_');
+    }
+
+    #[test]
+    fn locked_rule_changed() {
+        let mut lvl = level();
+        lvl.auto.rules[0].toggle_cell_lock(1);
+        let mut rule = lvl.auto.rules[0].clone();
+        rule.pattern[1] = b'y';
+        let errors = kinds(substitute_rules("rules", &lvl, vec![rule]).err().unwrap());
+        assert!(matches!(errors[..], [LockedRuleChanged { rule: 0 }]));
+
+        lvl.auto.rules[0].mutable = false;
+        let mut rule = lvl.auto.rules[0].clone();
+        rule.replace = b'y';
+        let errors = kinds(substitute_rules("rules", &lvl, vec![rule]).err().unwrap());
+        assert!(matches!(errors[..], [LockedRuleChanged { rule: 0 }]));
+    }
+
+    #[test]
+    fn symmetry_changed() {
+        let mut lvl = level();
+        let mut rule = lvl.auto.rules[0].clone();
+        rule.symmetry = Symmetry::Rot90;
+        let rules = vec![rule];
+        let errors = kinds(
+            substitute_rules("rules", &lvl, rules.clone())
+                .err()
+                .unwrap(),
+        );
+        assert!(matches!(errors[..], [SymmetryChanged { rule: 0 }]));
+        lvl.auto.allow_symmetry = true;
+        assert!(substitute_rules("rules", &lvl, rules).is_ok());
+    }
+
+    #[test]
+    fn counting_changed() {
+        let lvl = level();
+        let mut rule = lvl.auto.rules[0].clone();
+        rule.count = Some(Count {
+            of: b'x',
+            counts: vec![1],
+        });
+        let errors = kinds(substitute_rules("rules", &lvl, vec![rule]).err().unwrap());
+        assert!(matches!(errors[..], [CountingChanged { rule: 0 }]));
+    }
+
+    #[test]
+    fn duplicate_id() {
+        let levels = campaign(&[1, 2, 2]);
+        let e = validate_ids("dir", &levels).remove(0);
+        assert!(matches!(&e.kind, DuplicateId { id: 2, first } if first == "level2.ron"));
+        assert_eq!(e.path, "level2.ron");
+    }
+
+    #[test]
+    fn missing_ids() {
+        let errors = validate_ids("dir", &campaign(&[2, 3, 7]));
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].to_string(), "dir: no level with id 1");
+        assert_eq!(errors[1].to_string(), "dir: no levels with ids 4 to 6");
+    }
+
+    #[test]
+    fn huge_id_is_a_gap_not_an_allocation() {
+        let errors = kinds(validate_ids("dir", &campaign(&[1, usize::MAX])));
+        assert!(matches!(
+            errors[..],
+            [MissingIds { from: 2, to }] if to == usize::MAX - 1
+        ));
+    }
+
+    #[test]
+    fn zero_id() {
+        let errors = kinds(validate_ids("dir", &campaign(&[0, 1])));
+        assert!(matches!(errors[..], [ZeroId]));
+    }
+
+    #[test]
+    fn no_levels() {
+        let errors = kinds(validate_ids("dir", &[]));
+        assert!(matches!(errors[..], [NoLevels]));
+    }
+}